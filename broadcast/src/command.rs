@@ -1,11 +1,17 @@
-use maelstrom::{Error, Message};
+use crate::payload::Payloads;
+use crate::{digest::DigestMessage, epidemic::EpidemicMessage, plumtree::PlumtreeMessage};
+use crdt::{GossipMessage, Json};
+use maelstrom::{Error, Message, Topology};
 
 pub enum Command {
-    Topology(Vec<String>),
-    Broadcast(u64),
+    Topology(Topology),
+    Broadcast(Json),
+    Replicate(Json),
     Read,
-    Replicate(Vec<u64>),
-    ReplicateOk(String, Vec<u64>),
+    Gossip(GossipMessage<Payloads>),
+    Digest(DigestMessage),
+    Epidemic(EpidemicMessage),
+    Plumtree(PlumtreeMessage),
 }
 
 impl TryFrom<Message> for Command {
@@ -14,36 +20,34 @@ impl TryFrom<Message> for Command {
     fn try_from(value: Message) -> Result<Self, Self::Error> {
         match value.msg_type() {
             "topology" => topology(value),
-            "broadcast" => broadcast(value),
+            "broadcast" => broadcast(value).map(Command::Broadcast),
+            "replicate" => broadcast(value).map(Command::Replicate),
             "read" => read(value),
-            "replicate" => replicate(value),
-            "replicate_ok" => replicate_ok(value),
-            msg_type => Err(Error::not_supported(msg_type)),
+            "digest" | "digest_sync" | "digest_tick" => {
+                DigestMessage::try_from(value).map(Command::Digest)
+            }
+            "epidemic" | "epidemic_round" => {
+                EpidemicMessage::try_from(value).map(Command::Epidemic)
+            }
+            "plumtree_gossip" | "plumtree_ihave" | "plumtree_graft" | "plumtree_prune"
+            | "plumtree_tick" => PlumtreeMessage::try_from(value).map(Command::Plumtree),
+            _ => GossipMessage::try_from(value).map(Command::Gossip),
         }
     }
 }
 
 fn topology(message: Message) -> Result<Command, Error> {
-    let me = message.dest();
-
-    match message.body()["topology"][me].as_array() {
-        Some(neighbors) => {
-            let neighbors = neighbors
-                .iter()
-                .filter_map(|v| v.as_str())
-                .map(|s| s.to_string())
-                .collect();
-            Ok(Command::Topology(neighbors))
-        }
+    match message.body().get("topology") {
+        Some(topology) => Topology::try_from(topology).map(Command::Topology),
         None => Err(Error::malformed_request(
             "topology message missing `topology` key",
         )),
     }
 }
 
-fn broadcast(message: Message) -> Result<Command, Error> {
-    match message.body()["message"].as_u64() {
-        Some(value) => Ok(Command::Broadcast(value)),
+fn broadcast(message: Message) -> Result<Json, Error> {
+    match message.body().get("message") {
+        Some(value) => Ok(Json::new(value.clone())),
         _ => Err(Error::malformed_request(
             "broadcast message missing `message` key",
         )),
@@ -53,22 +57,3 @@ fn broadcast(message: Message) -> Result<Command, Error> {
 fn read(_: Message) -> Result<Command, Error> {
     Ok(Command::Read)
 }
-
-fn replicate(message: Message) -> Result<Command, Error> {
-    messages(message).map(Command::Replicate)
-}
-
-fn replicate_ok(message: Message) -> Result<Command, Error> {
-    let node_id = message.src().to_string();
-    messages(message).map(|messages| Command::ReplicateOk(node_id, messages))
-}
-
-fn messages(message: Message) -> Result<Vec<u64>, Error> {
-    match message.body()["messages"].as_array() {
-        Some(messages) => {
-            let values = messages.iter().filter_map(|v| v.as_u64()).collect();
-            Ok(values)
-        }
-        None => Ok(vec![]),
-    }
-}
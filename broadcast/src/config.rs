@@ -0,0 +1,205 @@
+use std::{env, str::FromStr, time::Duration};
+
+/// Tuning for the broadcast node, read from the environment so it can be
+/// changed per run:
+///
+/// - `BROADCAST_FLUSH_MS`: when set, replication is batched and flushed
+///   every that many milliseconds, instead of sent on every new value.
+/// - `BROADCAST_MAX_BATCH`: how many new values flush a batch early
+///   (default 100).
+/// - `BROADCAST_RESEND_MS`: how long to wait before resending values a
+///   neighbor hasn't acknowledged (default 200).
+/// - `BROADCAST_MAX_BACKOFF_MS`: the longest wait between resends to a
+///   neighbor that doesn't answer, as resends back off (default 2000).
+/// - `BROADCAST_TOPOLOGY`: the [Overlay] values are replicated over:
+///   `given` (the default), `spanning-tree`, `tree`, `grid` or `star`.
+/// - `BROADCAST_TREE_ARITY`: the number of children of each node in a
+///   `tree` overlay (default 4).
+/// - `BROADCAST_REPLICATION`: how values are replicated: `gossip` (the
+///   default), `digest`, `epidemic` or `plumtree`; see [Replication].
+/// - `BROADCAST_DIGEST_MS`: how often `digest` replication exchanges
+///   summaries with neighbors (default 300).
+/// - `BROADCAST_FANOUT`: how many random neighbors `epidemic` replication
+///   forwards values to each round (default 3).
+/// - `BROADCAST_ROUNDS`: how many rounds `epidemic` replication forwards
+///   each new value for (default 4).
+/// - `BROADCAST_ROUND_MS`: the length of an `epidemic` round (default 100).
+/// - `BROADCAST_SEED`: the seed for `epidemic` replication's choice of
+///   neighbors (default 0).
+/// - `BROADCAST_IHAVE_MS`: how often `plumtree` replication announces new
+///   values to lazy neighbors (default 100).
+/// - `BROADCAST_GRAFT_MS`: how long `plumtree` replication waits for an
+///   announced value before asking for it (default 300).
+/// - `BROADCAST_QUORUM`: when set, `broadcast_ok` is only sent once that
+///   many other nodes have acknowledged the value, instead of as soon as
+///   this node has it. This costs a `replicate` request to every other
+///   node per broadcast.
+/// - `BROADCAST_QUORUM_MS`: how long a broadcast waits for its quorum
+///   before failing (default 2000).
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub flush_interval: Option<Duration>,
+    pub max_batch: usize,
+    pub resend_interval: Duration,
+    pub max_backoff: Duration,
+    pub overlay: Overlay,
+    pub tree_arity: usize,
+    pub replication: Replication,
+    pub digest_interval: Duration,
+    pub fanout: usize,
+    pub rounds: usize,
+    pub round_interval: Duration,
+    pub seed: u64,
+    pub ihave_interval: Duration,
+    pub graft_timeout: Duration,
+    pub quorum: Option<usize>,
+    pub quorum_timeout: Duration,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            flush_interval: millis("BROADCAST_FLUSH_MS"),
+            max_batch: var("BROADCAST_MAX_BATCH").unwrap_or(default.max_batch),
+            resend_interval: millis("BROADCAST_RESEND_MS").unwrap_or(default.resend_interval),
+            max_backoff: millis("BROADCAST_MAX_BACKOFF_MS").unwrap_or(default.max_backoff),
+            overlay: var("BROADCAST_TOPOLOGY").unwrap_or(default.overlay),
+            tree_arity: var("BROADCAST_TREE_ARITY").unwrap_or(default.tree_arity),
+            replication: var("BROADCAST_REPLICATION").unwrap_or(default.replication),
+            digest_interval: millis("BROADCAST_DIGEST_MS").unwrap_or(default.digest_interval),
+            fanout: var("BROADCAST_FANOUT").unwrap_or(default.fanout),
+            rounds: var("BROADCAST_ROUNDS").unwrap_or(default.rounds),
+            round_interval: millis("BROADCAST_ROUND_MS").unwrap_or(default.round_interval),
+            seed: var("BROADCAST_SEED").unwrap_or(default.seed),
+            ihave_interval: millis("BROADCAST_IHAVE_MS").unwrap_or(default.ihave_interval),
+            graft_timeout: millis("BROADCAST_GRAFT_MS").unwrap_or(default.graft_timeout),
+            quorum: var("BROADCAST_QUORUM"),
+            quorum_timeout: millis("BROADCAST_QUORUM_MS").unwrap_or(default.quorum_timeout),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            flush_interval: None,
+            max_batch: 100,
+            resend_interval: Duration::from_millis(200),
+            max_backoff: Duration::from_millis(2000),
+            overlay: Overlay::Given,
+            tree_arity: 4,
+            replication: Replication::Gossip,
+            digest_interval: Duration::from_millis(300),
+            fanout: 3,
+            rounds: 4,
+            round_interval: Duration::from_millis(100),
+            seed: 0,
+            ihave_interval: Duration::from_millis(100),
+            graft_timeout: Duration::from_millis(300),
+            quorum: None,
+            quorum_timeout: Duration::from_millis(2000),
+        }
+    }
+}
+
+/// The graph values are replicated over. All overlays but `Given` ignore
+/// the neighbors Maelstrom suggests and are computed from the `node_ids` in
+/// `init`, with the first node as the root or hub.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overlay {
+    /// the topology from the `topology` message
+    Given,
+
+    /// a breadth-first spanning tree of the given topology
+    SpanningTree,
+
+    /// a tree with up to [Config::tree_arity] children per node
+    Tree,
+
+    /// a square-ish grid, each node connected to up to four others
+    Grid,
+
+    /// every node connected to a single hub
+    Star,
+}
+
+impl FromStr for Overlay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "given" => Ok(Self::Given),
+            "spanning-tree" => Ok(Self::SpanningTree),
+            "tree" => Ok(Self::Tree),
+            "grid" => Ok(Self::Grid),
+            "star" => Ok(Self::Star),
+            other => Err(format!("unknown topology: {}", other)),
+        }
+    }
+}
+
+/// How values are replicated to neighbors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Replication {
+    /// each neighbor is sent the values it hasn't acknowledged, until it
+    /// does; see [crdt::Gossip]
+    Gossip,
+
+    /// new values are pushed once, and lost ones are repaired by
+    /// exchanging summaries; see [AntiEntropy](crate::digest::AntiEntropy)
+    Digest,
+
+    /// new values are forwarded to a few random neighbors for a few
+    /// rounds; see [Epidemic](crate::epidemic::Epidemic)
+    Epidemic,
+
+    /// new values are pushed down a spanning tree and announced over the
+    /// other links; see [Plumtree](crate::plumtree::Plumtree)
+    Plumtree,
+}
+
+impl FromStr for Replication {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gossip" => Ok(Self::Gossip),
+            "digest" => Ok(Self::Digest),
+            "epidemic" => Ok(Self::Epidemic),
+            "plumtree" => Ok(Self::Plumtree),
+            other => Err(format!("unknown replication: {}", other)),
+        }
+    }
+}
+
+/// Parses an environment variable, if set.
+///
+/// # Panics
+///
+/// Panics if the variable is set to something that doesn't parse.
+fn var<T: FromStr>(name: &str) -> Option<T> {
+    let value = env::var(name).ok()?;
+    match value.parse() {
+        Ok(value) => Some(value),
+        Err(_) => panic!("invalid {}: {}", name, value),
+    }
+}
+
+/// Parses an environment variable holding milliseconds, if set.
+fn millis(name: &str) -> Option<Duration> {
+    var(name).map(Duration::from_millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_options() {
+        assert_eq!("spanning-tree".parse(), Ok(Overlay::SpanningTree));
+        assert_eq!("plumtree".parse(), Ok(Replication::Plumtree));
+        assert!("ring".parse::<Overlay>().is_err());
+        assert!("flood".parse::<Replication>().is_err());
+    }
+}
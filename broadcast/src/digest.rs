@@ -0,0 +1,310 @@
+use crate::payload::{fingerprint, Payloads};
+use crdt::{Crdt, Json};
+use maelstrom::{Context, Error, Message};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, HashSet},
+    time::Duration,
+};
+
+/// Integers are grouped into buckets of this many consecutive values.
+const BUCKET_SIZE: u64 = 64;
+
+/// Other values are grouped into this many buckets by hash, numbered from
+/// [OTHERS], above any bucket of integers.
+const OTHER_BUCKETS: u64 = 64;
+const OTHERS: u64 = 1 << 63;
+
+/// Replicates a set of values by digest-based anti-entropy.
+///
+/// New values are pushed to every neighbor once, without waiting for
+/// acknowledgements. To repair lost pushes, each node periodically sends
+/// its neighbors a [Summary] of its values; a neighbor that finds buckets
+/// that differ from its own replies with its values in those buckets, and
+/// the node answers with the values in them the neighbor lacks. Only
+/// differing buckets are ever transferred, so messages stay small however
+/// many values have been seen.
+pub struct AntiEntropy {
+    seen: Payloads,
+    neighbors: Vec<String>,
+    interval: Duration,
+}
+
+impl AntiEntropy {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            seen: Payloads::new(),
+            neighbors: vec![],
+            interval,
+        }
+    }
+
+    pub fn state(&self) -> &Payloads {
+        &self.seen
+    }
+
+    /// Starts the periodic digest exchange. Call this from
+    /// [Handler::init](maelstrom::Handler::init).
+    pub fn init(&self, ctx: &Context) {
+        ctx.notify_every(self.interval, json!({"type": "digest_tick"}));
+    }
+
+    pub fn set_neighbors(&mut self, neighbors: impl IntoIterator<Item = String>) {
+        self.neighbors = neighbors.into_iter().collect();
+    }
+
+    /// Adds a value, pushing it to all neighbors if it is new.
+    pub fn insert(&mut self, value: Json, ctx: &Context) {
+        if self.seen.insert(value.clone()) {
+            self.push(&Payloads::from_iter([value]), None, ctx);
+        }
+    }
+
+    pub fn handle(&mut self, message: DigestMessage, ctx: &Context) {
+        match message {
+            DigestMessage::Digest(summary) => {
+                let differing = Summary::of(&self.seen).differing(&summary);
+                if !differing.is_empty() {
+                    let values = self.values_in(&differing);
+                    let values = Value::from(values);
+                    let body = json!({"type": "digest_sync", "values": values, "want": differing});
+                    ctx.reply(body);
+                }
+            }
+            DigestMessage::Sync { values, want } => {
+                let missing = self.values_in(&want).delta(&values);
+                if !missing.is_empty() {
+                    let missing = Value::from(missing);
+                    let body = json!({"type": "digest_sync", "values": missing, "want": []});
+                    ctx.reply(body);
+                }
+
+                let new = values.delta(&self.seen);
+                self.seen.merge(&new);
+                self.push(&new, Some(ctx.src()), ctx);
+            }
+            DigestMessage::Tick => {
+                let summary = Value::from(&Summary::of(&self.seen));
+                for node_id in &self.neighbors {
+                    let body = json!({"type": "digest", "summary": summary});
+                    ctx.send(node_id.to_string(), None, body);
+                }
+            }
+        }
+    }
+
+    /// Pushes new values to every neighbor but `except`, if given.
+    fn push(&self, values: &Payloads, except: Option<&str>, ctx: &Context) {
+        if values.is_empty() {
+            return;
+        }
+
+        let values = Value::from(values.clone());
+        let body = json!({"type": "digest_sync", "values": values, "want": []});
+        for node_id in self
+            .neighbors
+            .iter()
+            .filter(|id| Some(id.as_str()) != except)
+        {
+            ctx.send(node_id.to_string(), None, body.clone());
+        }
+    }
+
+    /// The values seen in the given buckets. Integers are looked up by
+    /// range, so only the other values are walked.
+    fn values_in(&self, buckets: &[u64]) -> Payloads {
+        let mut values = Payloads::new();
+        let mut others = HashSet::new();
+        for bucket in buckets {
+            if *bucket >= OTHERS {
+                others.insert(*bucket);
+            } else if let Some(start) = bucket.checked_mul(BUCKET_SIZE) {
+                let end = start + (BUCKET_SIZE - 1);
+                for (start, end) in self.seen.integers().ranges_within(start, end) {
+                    values.insert_range(start, end);
+                }
+            }
+        }
+
+        if !others.is_empty() {
+            for value in self.seen.others().iter() {
+                if others.contains(&other_bucket(value).0) {
+                    values.insert(value.clone());
+                }
+            }
+        }
+        values
+    }
+}
+
+/// A compact summary of a set of values: for each bucket holding any, the
+/// number of values in it and an order-independent hash of them. Integers
+/// are hashed as the maximal ranges they form within their bucket, which
+/// equal sets share.
+///
+/// Encoded as an array of `[bucket, count, hash]` triples.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    buckets: BTreeMap<u64, (u64, u64)>,
+}
+
+impl Summary {
+    /// Summarizes `values`, taking integers a range at a time rather than
+    /// one by one.
+    pub fn of(values: &Payloads) -> Self {
+        let mut summary = Self::default();
+        for (start, end) in values.integers().ranges() {
+            for bucket in start / BUCKET_SIZE..=end / BUCKET_SIZE {
+                let first = start.max(bucket * BUCKET_SIZE);
+                let last = end.min(bucket * BUCKET_SIZE + (BUCKET_SIZE - 1));
+                summary.add(bucket, last - first + 1, mix(mix(first) ^ last));
+            }
+        }
+
+        for value in values.others().iter() {
+            let (bucket, hash) = other_bucket(value);
+            summary.add(bucket, 1, hash);
+        }
+        summary
+    }
+
+    /// Accounts for `count` values with the combined `hash` in `bucket`.
+    fn add(&mut self, bucket: u64, count: u64, value_hash: u64) {
+        let (total, hash) = self.buckets.entry(bucket).or_insert((0, 0));
+        *total += count;
+        *hash = value_hash.wrapping_add(*hash);
+    }
+
+    /// The buckets whose contents differ between `self` and `other`,
+    /// including those only one of them has values in.
+    pub fn differing(&self, other: &Summary) -> Vec<u64> {
+        let mut differing = self
+            .buckets
+            .iter()
+            .filter(|(bucket, summary)| other.buckets.get(bucket) != Some(summary))
+            .map(|(bucket, _)| *bucket)
+            .collect::<Vec<_>>();
+        differing.extend(
+            other
+                .buckets
+                .keys()
+                .filter(|bucket| !self.buckets.contains_key(bucket)),
+        );
+        differing
+    }
+}
+
+impl TryFrom<&Value> for Summary {
+    type Error = Error;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let invalid = || Error::malformed_request("summary must be [bucket, count, hash] triples");
+        let mut buckets = BTreeMap::new();
+        for triple in value.as_array().ok_or_else(invalid)? {
+            match (triple[0].as_u64(), triple[1].as_u64(), triple[2].as_u64()) {
+                (Some(bucket), Some(count), Some(hash)) => {
+                    buckets.insert(bucket, (count, hash));
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(Self { buckets })
+    }
+}
+
+impl From<&Summary> for Value {
+    fn from(summary: &Summary) -> Self {
+        summary
+            .buckets
+            .iter()
+            .map(|(bucket, (count, hash))| json!([bucket, count, hash]))
+            .collect()
+    }
+}
+
+/// The bucket a value other than an integer falls in, and its hash.
+fn other_bucket(value: &Json) -> (u64, u64) {
+    let hash = mix(fingerprint(value));
+    (OTHERS + hash % OTHER_BUCKETS, hash)
+}
+
+/// Scrambles a value (the splitmix64 finalizer), so that sums of distinct
+/// sets of values rarely collide.
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// The messages handled by [AntiEntropy].
+pub enum DigestMessage {
+    Digest(Summary),
+    Sync { values: Payloads, want: Vec<u64> },
+    Tick,
+}
+
+impl TryFrom<Message> for DigestMessage {
+    type Error = Error;
+
+    fn try_from(message: Message) -> Result<Self, Self::Error> {
+        let body = message.body();
+        match message.msg_type() {
+            "digest" => Summary::try_from(&body["summary"]).map(DigestMessage::Digest),
+            "digest_sync" => Ok(DigestMessage::Sync {
+                values: Payloads::try_from(body["values"].clone())?,
+                want: integers(&body["want"])?,
+            }),
+            "digest_tick" => Ok(DigestMessage::Tick),
+            msg_type => Err(Error::not_supported(msg_type)),
+        }
+    }
+}
+
+fn integers(value: &Value) -> Result<Vec<u64>, Error> {
+    value
+        .as_array()
+        .and_then(|values| values.iter().map(Value::as_u64).collect())
+        .ok_or_else(|| Error::malformed_request("expected an array of integers"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payloads(values: impl IntoIterator<Item = Value>) -> Payloads {
+        values.into_iter().map(Json::from).collect()
+    }
+
+    #[test]
+    fn summaries_find_differing_buckets() {
+        let values = (0..300)
+            .map(Value::from)
+            .chain([json!("a"), json!({"b": 1})]);
+        let all = payloads(values.clone());
+        let summary = Summary::of(&all);
+        assert_eq!(summary, Summary::of(&payloads(values.clone().rev())));
+
+        let without = payloads(values.filter(|value| *value != json!(100)));
+        assert_eq!(summary.differing(&Summary::of(&without)), vec![1]);
+
+        let without = payloads((0..300).map(Value::from).chain([json!("a")]));
+        let differing = summary.differing(&Summary::of(&without));
+        let [bucket] = differing[..] else {
+            panic!("expected one differing bucket, got {:?}", differing);
+        };
+        assert!(bucket >= OTHERS);
+    }
+
+    #[test]
+    fn looks_up_values_by_bucket() {
+        let mut anti_entropy = AntiEntropy::new(Duration::from_secs(1));
+        let values = (0..300).map(Value::from).chain([json!("a")]);
+        anti_entropy.seen = payloads(values);
+
+        let (other, _) = other_bucket(&Json::from(json!("a")));
+        let found = anti_entropy.values_in(&[1, 4, 9, other]);
+        let expected = (64..128).chain(256..300).map(Value::from);
+        assert_eq!(found, payloads(expected.chain([json!("a")])));
+    }
+}
@@ -0,0 +1,159 @@
+use crate::payload::Payloads;
+use crdt::{Crdt, Json};
+use maelstrom::{Context, Error, Message};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde_json::{json, Value};
+use std::{collections::VecDeque, time::Duration};
+
+/// Replicates a set of values by probabilistic epidemic gossip.
+///
+/// Every round, each node forwards the values it has learned in the last
+/// few rounds to a random few of its neighbors, then forgets about values
+/// that have been forwarded for the configured number of rounds. Nothing is
+/// acknowledged: values reach every node with high probability, trading
+/// convergence time for message count through the fanout and rounds.
+///
+/// Neighbors are picked with a random number generator seeded from the
+/// configured seed and the node's position in the cluster, so runs are
+/// reproducible.
+pub struct Epidemic {
+    seen: Payloads,
+    neighbors: Vec<String>,
+    fanout: usize,
+    rounds: usize,
+    interval: Duration,
+    seed: u64,
+    rng: StdRng,
+
+    /// values learned since the last round
+    fresh: Payloads,
+
+    /// the values learned in each of the past rounds, most recent first
+    active: VecDeque<Payloads>,
+}
+
+impl Epidemic {
+    pub fn new(fanout: usize, rounds: usize, interval: Duration, seed: u64) -> Self {
+        Self {
+            seen: Payloads::new(),
+            neighbors: vec![],
+            fanout,
+            rounds,
+            interval,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            fresh: Payloads::new(),
+            active: VecDeque::new(),
+        }
+    }
+
+    pub fn state(&self) -> &Payloads {
+        &self.seen
+    }
+
+    /// Seeds the neighbor choices for this node and starts the rounds. Call
+    /// this from [Handler::init](maelstrom::Handler::init).
+    pub fn init(&mut self, ctx: &Context) {
+        let node_ids = ctx.node_ids();
+        let position = node_ids.iter().position(|id| id == ctx.node_id());
+        self.seed_for(position.unwrap_or_default());
+
+        ctx.notify_every(self.interval, json!({"type": "epidemic_round"}));
+    }
+
+    /// Seeds the neighbor choices for the node at `position` in the cluster.
+    fn seed_for(&mut self, position: usize) {
+        let seed = self.seed.wrapping_add(position as u64);
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn set_neighbors(&mut self, neighbors: impl IntoIterator<Item = String>) {
+        self.neighbors = neighbors.into_iter().collect();
+    }
+
+    /// Adds a value, to be forwarded from the next round if it is new.
+    pub fn insert(&mut self, value: Json) {
+        if self.seen.insert(value.clone()) {
+            self.fresh.insert(value);
+        }
+    }
+
+    pub fn handle(&mut self, message: EpidemicMessage, ctx: &Context) {
+        match message {
+            EpidemicMessage::Values(values) => {
+                let new = values.delta(&self.seen);
+                self.seen.merge(&new);
+                self.fresh.merge(&new);
+            }
+            EpidemicMessage::Round => self.round(ctx),
+        }
+    }
+
+    /// Forwards the active values to `fanout` random neighbors, and prunes
+    /// those that have been forwarded for `rounds` rounds.
+    fn round(&mut self, ctx: &Context) {
+        self.active.push_front(std::mem::take(&mut self.fresh));
+        self.active.truncate(self.rounds);
+
+        let mut values = Payloads::new();
+        for active in &self.active {
+            values.merge(active);
+        }
+        if values.is_empty() {
+            return;
+        }
+
+        let body = json!({"type": "epidemic", "values": Value::from(values)});
+        for node_id in self.targets() {
+            ctx.send(node_id, None, body.clone());
+        }
+    }
+
+    /// Picks the neighbors to forward to this round.
+    fn targets(&mut self) -> Vec<String> {
+        self.neighbors
+            .choose_multiple(&mut self.rng, self.fanout)
+            .cloned()
+            .collect()
+    }
+}
+
+/// The messages handled by [Epidemic].
+pub enum EpidemicMessage {
+    Values(Payloads),
+    Round,
+}
+
+impl TryFrom<Message> for EpidemicMessage {
+    type Error = Error;
+
+    fn try_from(message: Message) -> Result<Self, Self::Error> {
+        match message.msg_type() {
+            "epidemic" => {
+                Payloads::try_from(message.body()["values"].clone()).map(EpidemicMessage::Values)
+            }
+            "epidemic_round" => Ok(EpidemicMessage::Round),
+            msg_type => Err(Error::not_supported(msg_type)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The neighbors picked in a few rounds by the node at `position`.
+    fn picks(position: usize) -> Vec<Vec<String>> {
+        let mut epidemic = Epidemic::new(2, 4, Duration::from_secs(1), 7);
+        epidemic.set_neighbors((0..10).map(|i| format!("n{}", i)));
+        epidemic.seed_for(position);
+        (0..20).map(|_| epidemic.targets()).collect()
+    }
+
+    #[test]
+    fn seeded_picks_are_reproducible() {
+        assert_eq!(picks(3), picks(3));
+        assert_ne!(picks(3), picks(4));
+        assert!(picks(3).iter().all(|targets| targets.len() == 2));
+    }
+}
@@ -1,78 +1,150 @@
-use crate::command::Command;
-use maelstrom::{Context, Handler};
-use serde_json::{json, Value};
-use std::collections::{HashMap, HashSet};
+use crate::{
+    command::Command,
+    config::{Config, Overlay, Replication},
+    digest::{AntiEntropy, DigestMessage},
+    epidemic::{Epidemic, EpidemicMessage},
+    payload::Payloads,
+    plumtree::{Plumtree, PlumtreeMessage},
+    quorum,
+};
+use crdt::{Element, Gossip, GossipMessage, Json};
+use maelstrom::{Context, Handler, Topology};
+use serde_json::json;
+use std::time::Duration;
 
 pub struct BroadcastHandler {
-    seen: HashSet<u64>,
-    neighbors: HashMap<String, HashSet<u64>>,
+    seen: Seen,
+    overlay: Overlay,
+    tree_arity: usize,
+    quorum: Option<usize>,
+    quorum_timeout: Duration,
+}
+
+/// The values seen so far, replicated as configured.
+enum Seen {
+    Gossip(Gossip<Payloads>),
+    Digest(AntiEntropy),
+    Epidemic(Box<Epidemic>),
+    Plumtree(Plumtree),
 }
 
 impl BroadcastHandler {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        let seen = match config.replication {
+            Replication::Gossip => {
+                let gossip = Gossip::new()
+                    .with_interval(config.resend_interval)
+                    .with_max_backoff(config.max_backoff);
+                Seen::Gossip(match config.flush_interval {
+                    Some(flush_interval) => gossip.with_batching(flush_interval, config.max_batch),
+                    None => gossip,
+                })
+            }
+            Replication::Digest => Seen::Digest(AntiEntropy::new(config.digest_interval)),
+            Replication::Epidemic => Seen::Epidemic(Box::new(Epidemic::new(
+                config.fanout,
+                config.rounds,
+                config.round_interval,
+                config.seed,
+            ))),
+            Replication::Plumtree => {
+                Seen::Plumtree(Plumtree::new(config.ihave_interval, config.graft_timeout))
+            }
+        };
         Self {
-            seen: Default::default(),
-            neighbors: Default::default(),
+            seen,
+            overlay: config.overlay,
+            tree_arity: config.tree_arity,
+            quorum: config.quorum,
+            quorum_timeout: config.quorum_timeout,
         }
     }
 
-    fn topology(&mut self, neighbors: Vec<String>, ctx: Context) {
-        self.neighbors.extend(
-            neighbors
-                .into_iter()
-                .map(|node_id| (node_id, HashSet::new())),
-        );
+    fn topology(&mut self, topology: Topology, ctx: Context) {
+        let topology = self.overlay(topology, &ctx);
+        let neighbors = topology.neighbors(ctx.node_id()).iter().cloned();
+        match &mut self.seen {
+            Seen::Gossip(gossip) => gossip.set_neighbors(neighbors),
+            Seen::Digest(digest) => digest.set_neighbors(neighbors),
+            Seen::Epidemic(epidemic) => epidemic.set_neighbors(neighbors),
+            Seen::Plumtree(plumtree) => plumtree.set_neighbors(neighbors),
+        }
+        ctx.set_topology(&topology);
 
         let reply = json!({ "type": "topology_ok"});
         ctx.reply(reply);
     }
 
-    fn broadcast(&mut self, value: u64, ctx: Context) {
-        self.add_seen(&[value], &ctx);
+    /// The graph to replicate over, given the one Maelstrom suggests.
+    fn overlay(&self, given: Topology, ctx: &Context) -> Topology {
+        let node_ids = ctx.node_ids();
+        match (self.overlay, node_ids.first()) {
+            (Overlay::SpanningTree, Some(root)) => given.spanning_tree(root),
+            (Overlay::Tree, _) => Topology::tree(node_ids, self.tree_arity),
+            (Overlay::Grid, _) => Topology::grid(node_ids),
+            (Overlay::Star, _) => Topology::star(node_ids),
+            (Overlay::Given | Overlay::SpanningTree, _) => given,
+        }
+    }
 
-        let reply = json!({ "type": "broadcast_ok"});
-        ctx.reply(reply);
+    fn broadcast(&mut self, value: Json, ctx: Context) {
+        self.insert(value.clone(), &ctx);
+
+        match self.quorum {
+            Some(quorum) => quorum::replicate(value, quorum, self.quorum_timeout, ctx),
+            None => ctx.reply(json!({ "type": "broadcast_ok"})),
+        }
     }
 
-    fn read(&mut self, ctx: Context) {
-        let messages = self.seen.iter().cloned().collect::<Value>();
-        let reply = json!({ "type":"read_ok", "messages": messages});
-        ctx.reply(reply)
+    /// Stores a value another node is waiting on this one to acknowledge.
+    fn replicate(&mut self, value: Json, ctx: Context) {
+        self.insert(value, &ctx);
+        ctx.reply(json!({ "type": "replicate_ok"}));
     }
 
-    fn replicate(&mut self, values: Vec<u64>, ctx: Context) {
-        self.add_seen(&values, &ctx);
+    fn insert(&mut self, value: Json, ctx: &Context) {
+        match &mut self.seen {
+            Seen::Gossip(gossip) => gossip.update(|seen| _ = seen.insert(value), ctx),
+            Seen::Digest(digest) => digest.insert(value, ctx),
+            Seen::Epidemic(epidemic) => epidemic.insert(value),
+            Seen::Plumtree(plumtree) => plumtree.insert(value, ctx),
+        }
+    }
 
-        let messages = values.into_iter().collect::<Value>();
-        let reply = json!({"type":"replicate_ok", "messages": messages});
+    fn read(&mut self, ctx: Context) {
+        let seen = match &self.seen {
+            Seen::Gossip(gossip) => gossip.state(),
+            Seen::Digest(digest) => digest.state(),
+            Seen::Epidemic(epidemic) => epidemic.state(),
+            Seen::Plumtree(plumtree) => plumtree.state(),
+        };
+        // Maelstrom expects the values themselves, not their ranges
+        let messages = seen.iter().map(|value| value.to_json()).collect::<Vec<_>>();
+        let reply = json!({ "type":"read_ok", "messages": messages});
         ctx.reply(reply)
     }
 
-    fn replicate_ok(&mut self, node_id: String, values: Vec<u64>) {
-        if let Some(pending) = self.neighbors.get_mut(&node_id) {
-            pending.retain(|v| !values.contains(v));
+    fn gossip(&mut self, message: GossipMessage<Payloads>, ctx: Context) {
+        if let Seen::Gossip(gossip) = &mut self.seen {
+            gossip.handle(message, &ctx);
         }
     }
 
-    fn add_seen(&mut self, values: &[u64], ctx: &Context) {
-        let mut modified = false;
-
-        for value in values {
-            if self.seen.insert(*value) {
-                modified = true;
+    fn digest(&mut self, message: DigestMessage, ctx: Context) {
+        if let Seen::Digest(digest) = &mut self.seen {
+            digest.handle(message, &ctx);
+        }
+    }
 
-                for pending in self.neighbors.values_mut() {
-                    pending.insert(*value);
-                }
-            }
+    fn epidemic(&mut self, message: EpidemicMessage, ctx: Context) {
+        if let Seen::Epidemic(epidemic) = &mut self.seen {
+            epidemic.handle(message, &ctx);
         }
+    }
 
-        if modified {
-            for (node_id, pending) in self.neighbors.iter() {
-                let messages = pending.iter().cloned().collect::<Value>();
-                let replicate = json!({"type": "replicate", "messages": messages});
-                ctx.send(node_id.to_string(), None, replicate);
-            }
+    fn plumtree(&mut self, message: PlumtreeMessage, ctx: Context) {
+        if let Seen::Plumtree(plumtree) = &mut self.seen {
+            plumtree.handle(message, &ctx);
         }
     }
 }
@@ -80,13 +152,221 @@ impl BroadcastHandler {
 impl Handler for BroadcastHandler {
     type Command = Command;
 
+    fn init(&mut self, ctx: Context) {
+        match &mut self.seen {
+            Seen::Gossip(gossip) => gossip.init(&ctx),
+            Seen::Digest(digest) => digest.init(&ctx),
+            Seen::Epidemic(epidemic) => epidemic.init(&ctx),
+            Seen::Plumtree(plumtree) => plumtree.init(&ctx),
+        }
+    }
+
     fn handle(&mut self, command: Command, ctx: Context) {
         match command {
-            Command::Topology(neighbors) => self.topology(neighbors, ctx),
+            Command::Topology(topology) => self.topology(topology, ctx),
             Command::Broadcast(value) => self.broadcast(value, ctx),
+            Command::Replicate(value) => self.replicate(value, ctx),
             Command::Read => self.read(ctx),
-            Command::Replicate(values) => self.replicate(values, ctx),
-            Command::ReplicateOk(node_id, values) => self.replicate_ok(node_id, values),
+            Command::Gossip(message) => self.gossip(message, ctx),
+            Command::Digest(message) => self.digest(message, ctx),
+            Command::Epidemic(message) => self.epidemic(message, ctx),
+            Command::Plumtree(message) => self.plumtree(message, ctx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maelstrom::testing::Network;
+    use serde_json::Value;
+    use std::time::Duration;
+    use tokio::time::sleep;
+
+    const NODES: [&str; 5] = ["n0", "n1", "n2", "n3", "n4"];
+
+    /// Starts a cluster replicating over the line n0 - n1 - n2 - n3 - n4.
+    async fn cluster(config: Config) -> Network {
+        let network = Network::new();
+        for node_id in NODES {
+            network.start(node_id, BroadcastHandler::new(&config));
+        }
+        network.init(&NODES).await;
+
+        let topology = NODES
+            .iter()
+            .enumerate()
+            .map(|(i, node_id)| {
+                let neighbors = [i.checked_sub(1), Some(i + 1)]
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|j| NODES.get(j))
+                    .map(|neighbor| json!(neighbor));
+                (node_id.to_string(), neighbors.collect::<Value>())
+            })
+            .collect::<serde_json::Map<_, _>>();
+        for node_id in NODES {
+            let body = json!({"type": "topology", "topology": topology});
+            network.request(node_id, body).await;
         }
+        network
+    }
+
+    async fn broadcast(network: &Network, node_id: &str, message: Value) {
+        let body = json!({"type": "broadcast", "message": message});
+        let reply = network.request(node_id, body).await;
+        assert_eq!(reply["type"], "broadcast_ok");
+    }
+
+    async fn read(network: &Network, node_id: &str) -> Vec<Value> {
+        let reply = network.request(node_id, json!({"type": "read"})).await;
+        let mut messages = reply["messages"].as_array().unwrap().clone();
+        messages.sort_by_key(Value::to_string);
+        messages
+    }
+
+    /// Waits until every node has read exactly `expected`.
+    async fn converge(network: &Network, expected: impl IntoIterator<Item = Value>) {
+        let mut expected = expected.into_iter().collect::<Vec<_>>();
+        expected.sort_by_key(Value::to_string);
+
+        let mut reads = vec![];
+        for _ in 0..150 {
+            reads.clear();
+            for node_id in NODES {
+                reads.push(read(network, node_id).await);
+            }
+            if reads.iter().all(|read| *read == expected) {
+                return;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+
+        panic!("nodes did not converge to {:?}, read {:?}", expected, reads);
+    }
+
+    /// Broadcasts `count` integers through nodes in turn and waits for every
+    /// node to have them all.
+    async fn replicates(network: &Network, count: u64) {
+        for (value, node_id) in (0..count).zip(NODES.iter().cycle()) {
+            broadcast(network, node_id, json!(value)).await;
+        }
+        converge(network, (0..count).map(Value::from)).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn gossip_replicates_in_batches() {
+        let config = Config {
+            flush_interval: Some(Duration::from_millis(50)),
+            max_batch: 5,
+            ..Config::default()
+        };
+        let network = cluster(config).await;
+        replicates(&network, 12).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn gossip_heals_partitions() {
+        let config = Config {
+            resend_interval: Duration::from_millis(20),
+            max_backoff: Duration::from_millis(100),
+            ..Config::default()
+        };
+        let network = cluster(config).await;
+
+        // cut the line in the middle
+        network.isolate("n2");
+        broadcast(&network, "n0", json!(1)).await;
+        broadcast(&network, "n4", json!(2)).await;
+        // long enough for resends to back off to the maximum
+        sleep(Duration::from_millis(300)).await;
+        assert_eq!(read(&network, "n4").await, vec![json!(2)]);
+
+        network.heal();
+        converge(&network, [json!(1), json!(2)]).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn digests_repair_lost_pushes() {
+        let config = Config {
+            replication: Replication::Digest,
+            digest_interval: Duration::from_millis(50),
+            ..Config::default()
+        };
+        let network = cluster(config).await;
+        replicates(&network, 12).await;
+
+        // pushes to and from n2 are lost, and only digests bring them back
+        network.isolate("n2");
+        broadcast(&network, "n0", json!(12)).await;
+        broadcast(&network, "n4", json!("a")).await;
+        network.heal();
+
+        let expected = (0..13).map(Value::from).chain([json!("a")]);
+        converge(&network, expected).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn epidemic_rounds_reach_every_node() {
+        // grid nodes have two or three neighbors, of which only one is
+        // picked each round
+        let config = Config {
+            replication: Replication::Epidemic,
+            overlay: Overlay::Grid,
+            fanout: 1,
+            rounds: 20,
+            round_interval: Duration::from_millis(10),
+            ..Config::default()
+        };
+        let network = cluster(config).await;
+        replicates(&network, 12).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn plumtree_replicates_over_cycles() {
+        // the grid has cycles, so some links get pruned to lazy ones
+        let config = Config {
+            replication: Replication::Plumtree,
+            overlay: Overlay::Grid,
+            ihave_interval: Duration::from_millis(20),
+            graft_timeout: Duration::from_millis(60),
+            ..Config::default()
+        };
+        let network = cluster(config).await;
+        replicates(&network, 12).await;
+
+        network.isolate("n1");
+        broadcast(&network, "n0", json!(12)).await;
+        network.heal();
+        replicates(&network, 20).await;
+
+        // values other than integers are announced by fingerprint
+        broadcast(&network, "n4", json!({"a": [1, 2]})).await;
+        let expected = (0..20).map(Value::from).chain([json!({"a": [1, 2]})]);
+        converge(&network, expected).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn quorum_broadcasts_wait_for_acknowledgements() {
+        let config = Config {
+            quorum: Some(2),
+            quorum_timeout: Duration::from_millis(300),
+            resend_interval: Duration::from_millis(20),
+            max_backoff: Duration::from_millis(100),
+            ..Config::default()
+        };
+        let network = cluster(config).await;
+        replicates(&network, 5).await;
+
+        // no peer can acknowledge, so the broadcast fails at the deadline
+        network.isolate("n0");
+        let body = json!({"type": "broadcast", "message": 5});
+        let reply = network.request("n0", body).await;
+        assert_eq!(reply["type"], "error");
+        assert_eq!(reply["code"], 11, "expected temporarily unavailable");
+
+        // but the value is still replicated once the partition heals
+        network.heal();
+        converge(&network, (0..6).map(Value::from)).await;
     }
 }
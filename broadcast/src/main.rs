@@ -1,11 +1,21 @@
 mod command;
+mod config;
+mod digest;
+mod epidemic;
 mod handler;
+mod payload;
+mod plumtree;
+mod quorum;
 
+use config::Config;
 use handler::BroadcastHandler;
 use maelstrom::Node;
 use tokio::task::JoinError;
 
 #[tokio::main]
 async fn main() -> Result<(), JoinError> {
-    Node::from_handler(BroadcastHandler::new()).start().await
+    let config = Config::from_env();
+    Node::from_handler(BroadcastHandler::new(&config))
+        .start()
+        .await
 }
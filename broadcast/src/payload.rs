@@ -0,0 +1,148 @@
+use crdt::{Crdt, Element, GSet, IntervalSet, Json};
+use maelstrom::{fnv1a, Error};
+use serde_json::{json, Value};
+
+/// The set of values broadcast so far: unsigned integers, which the
+/// workload sends, kept as compact ranges, and any other JSON values,
+/// deduplicated by canonical encoding.
+///
+/// Encoded as `{"integers": ranges, "others": values}`, with the integers
+/// as an [IntervalSet].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Payloads {
+    integers: IntervalSet,
+    others: GSet<Json>,
+}
+
+impl Payloads {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds all integers from `start` to `end`, inclusive.
+    pub fn insert_range(&mut self, start: u64, end: u64) {
+        self.integers.insert_range(start, end);
+    }
+
+    /// Adds a value, returning whether it is new.
+    pub fn insert(&mut self, payload: Json) -> bool {
+        match payload.value().as_u64() {
+            Some(integer) => self.integers.insert(integer),
+            None => self.others.insert(payload),
+        }
+    }
+
+    pub fn contains(&self, payload: &Json) -> bool {
+        match payload.value().as_u64() {
+            Some(integer) => self.integers.contains(integer),
+            None => self.others.contains(payload),
+        }
+    }
+
+    pub fn integers(&self) -> &IntervalSet {
+        &self.integers
+    }
+
+    /// The values that aren't unsigned integers.
+    pub fn others(&self) -> &GSet<Json> {
+        &self.others
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.integers.is_empty() && self.others.is_empty()
+    }
+
+    /// The values in the set, integers first.
+    pub fn iter(&self) -> impl Iterator<Item = Json> + '_ {
+        let integers = self
+            .integers
+            .iter()
+            .map(|integer| Json::from(json!(integer)));
+        integers.chain(self.others.iter().cloned())
+    }
+}
+
+/// The [fnv1a] hash of a value's canonical encoding, which equal values
+/// share.
+pub fn fingerprint(payload: &Json) -> u64 {
+    fnv1a(payload.canonical().as_bytes())
+}
+
+impl FromIterator<Json> for Payloads {
+    fn from_iter<I: IntoIterator<Item = Json>>(iter: I) -> Self {
+        let mut payloads = Self::new();
+        for payload in iter {
+            payloads.insert(payload);
+        }
+        payloads
+    }
+}
+
+impl Crdt for Payloads {
+    fn merge(&mut self, other: &Self) {
+        self.integers.merge(&other.integers);
+        self.others.merge(&other.others);
+    }
+
+    fn delta(&self, since: &Self) -> Self {
+        Self {
+            integers: self.integers.delta(&since.integers),
+            others: self.others.delta(&since.others),
+        }
+    }
+}
+
+impl TryFrom<Value> for Payloads {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let payloads = Self {
+            integers: IntervalSet::try_from(value["integers"].clone())?,
+            others: GSet::try_from(value["others"].clone())?,
+        };
+
+        // integers sent among the others would never match local ones
+        if payloads.others.iter().any(|other| other.value().is_u64()) {
+            return Err(Error::malformed_request("integers must be sent as ranges"));
+        }
+        Ok(payloads)
+    }
+}
+
+impl From<Payloads> for Value {
+    fn from(payloads: Payloads) -> Self {
+        let others = payloads
+            .others
+            .iter()
+            .map(Json::to_json)
+            .collect::<Vec<_>>();
+        json!({"integers": Value::from(payloads.integers), "others": others})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deduplicates_by_canonical_encoding() {
+        let mut payloads = Payloads::new();
+        assert!(payloads.insert(Json::from(json!({"a": 1, "b": [2, 3]}))));
+        assert!(!payloads.insert(Json::from(json!({"b": [2, 3], "a": 1}))));
+        assert!(payloads.insert(Json::from(json!({"a": 1, "b": [3, 2]}))));
+        assert!(payloads.insert(Json::from(json!(7))));
+        assert!(!payloads.insert(Json::from(json!(7))));
+        assert!(payloads.insert(Json::from(json!("7"))));
+
+        let value = Value::from(payloads.clone());
+        assert_eq!(value["integers"], json!([[7, 7]]));
+        assert_eq!(value["others"].as_array().unwrap().len(), 3);
+        assert_eq!(Payloads::try_from(value).unwrap(), payloads);
+    }
+
+    #[test]
+    fn rejects_integers_among_others() {
+        let value = json!({"integers": [], "others": [7]});
+        assert!(Payloads::try_from(value).is_err());
+    }
+}
@@ -0,0 +1,318 @@
+use crate::payload::{fingerprint, Payloads};
+use crdt::{Crdt, IntervalSet, Json};
+use maelstrom::{Context, Error, Message};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+/// Replicates a set of values with Plumtree, epidemic broadcast trees.
+///
+/// Values are pushed eagerly to a subset of the neighbors that forms a
+/// spanning tree, and only announced, in periodic `IHAVE` batches, to the
+/// rest. A node that receives values it already has prunes the link they
+/// came over to a lazy one; a node that hears of values it doesn't receive
+/// in time grafts the link they were announced over back into the tree and
+/// asks for them. Starting from every neighbor eager, the tree settles on
+/// the fastest links and heals around lost ones. Announcements and grafts
+/// name values by their [Ids] rather than carrying them.
+///
+/// All messages are sent reliably, as the protocol expects of its links.
+pub struct Plumtree {
+    seen: Payloads,
+
+    /// the values in `seen` other than integers, by fingerprint
+    others: HashMap<u64, Json>,
+
+    /// neighbors new values are pushed to
+    eager: Vec<String>,
+
+    /// neighbors new values are announced to
+    lazy: Vec<String>,
+
+    /// values not yet announced, by neighbor
+    announce: HashMap<String, Ids>,
+
+    /// values heard of but not received: when to ask for them, and the
+    /// neighbors that have them, in the order they were heard from
+    missing: BTreeMap<Id, (Instant, VecDeque<String>)>,
+
+    interval: Duration,
+    graft_timeout: Duration,
+}
+
+impl Plumtree {
+    pub fn new(interval: Duration, graft_timeout: Duration) -> Self {
+        Self {
+            seen: Payloads::new(),
+            others: HashMap::new(),
+            eager: vec![],
+            lazy: vec![],
+            announce: HashMap::new(),
+            missing: BTreeMap::new(),
+            interval,
+            graft_timeout,
+        }
+    }
+
+    pub fn state(&self) -> &Payloads {
+        &self.seen
+    }
+
+    /// Starts the periodic announcements and grafts. Call this from
+    /// [Handler::init](maelstrom::Handler::init).
+    pub fn init(&self, ctx: &Context) {
+        ctx.notify_every(self.interval, json!({"type": "plumtree_tick"}));
+    }
+
+    /// Starts over with every neighbor eager.
+    pub fn set_neighbors(&mut self, neighbors: impl IntoIterator<Item = String>) {
+        self.eager = neighbors.into_iter().collect();
+        self.lazy.clear();
+        self.announce.clear();
+    }
+
+    /// Adds a value, pushing it down the tree if it is new.
+    pub fn insert(&mut self, value: Json, ctx: &Context) {
+        if self.seen.insert(value.clone()) {
+            if !value.value().is_u64() {
+                self.others.insert(fingerprint(&value), value.clone());
+            }
+            self.push(Payloads::from_iter([value]), ctx.node_id(), ctx);
+        }
+    }
+
+    pub fn handle(&mut self, message: PlumtreeMessage, ctx: &Context) {
+        let src = ctx.src().to_string();
+        match message {
+            PlumtreeMessage::Gossip(values) => {
+                let new = values.delta(&self.seen);
+                if new.is_empty() {
+                    self.make_lazy(&src);
+                    ctx.send_reliable(src, json!({"type": "plumtree_prune"}));
+                    return;
+                }
+
+                self.seen.merge(&new);
+                for value in new.others().iter() {
+                    self.others.insert(fingerprint(value), value.clone());
+                }
+                for value in new.iter() {
+                    self.missing.remove(&Id::of(&value));
+                }
+                self.make_eager(&src);
+                self.push(new, &src, ctx);
+            }
+            PlumtreeMessage::IHave(ids) => {
+                let deadline = Instant::now() + self.graft_timeout;
+                let integers = ids.integers.delta(self.seen.integers());
+                let others = ids
+                    .others
+                    .into_iter()
+                    .filter(|hash| !self.others.contains_key(hash));
+                let unseen = integers
+                    .iter()
+                    .map(Id::Integer)
+                    .chain(others.map(Id::Other));
+                for id in unseen.collect::<Vec<_>>() {
+                    let (_, announcers) = self
+                        .missing
+                        .entry(id)
+                        .or_insert_with(|| (deadline, VecDeque::new()));
+                    announcers.push_back(src.clone());
+                }
+            }
+            PlumtreeMessage::Graft(ids) => {
+                self.make_eager(&src);
+                let mut have = Payloads::new();
+                for (start, end) in ids.integers.ranges() {
+                    for (start, end) in self.seen.integers().ranges_within(start, end) {
+                        have.insert_range(start, end);
+                    }
+                }
+                for hash in &ids.others {
+                    if let Some(value) = self.others.get(hash) {
+                        have.insert(value.clone());
+                    }
+                }
+                if !have.is_empty() {
+                    let body = json!({"type": "plumtree_gossip", "values": Value::from(have)});
+                    ctx.send_reliable(src, body);
+                }
+            }
+            PlumtreeMessage::Prune => self.make_lazy(&src),
+            PlumtreeMessage::Tick => {
+                self.flush_announcements(ctx);
+                self.graft(ctx);
+            }
+        }
+    }
+
+    /// Pushes new values to the eager neighbors and queues them to be
+    /// announced to the lazy ones, except to `src`, where they came from.
+    fn push(&mut self, values: Payloads, src: &str, ctx: &Context) {
+        let body = json!({"type": "plumtree_gossip", "values": Value::from(values.clone())});
+        for node_id in self.eager.iter().filter(|id| *id != src) {
+            ctx.send_reliable(node_id.to_string(), body.clone());
+        }
+        for node_id in self.lazy.iter().filter(|id| *id != src) {
+            let announce = self.announce.entry(node_id.to_string()).or_default();
+            for value in values.iter() {
+                announce.insert(Id::of(&value));
+            }
+        }
+    }
+
+    fn flush_announcements(&mut self, ctx: &Context) {
+        for (node_id, ids) in self.announce.drain() {
+            let body = json!({"type": "plumtree_ihave", "ids": Value::from(ids)});
+            ctx.send_reliable(node_id, body);
+        }
+    }
+
+    /// Asks for the values that are overdue from the first neighbor that
+    /// announced each, grafting it into the tree, and gives the next one
+    /// until another timeout to answer.
+    fn graft(&mut self, ctx: &Context) {
+        let now = Instant::now();
+        let mut grafts: HashMap<String, Ids> = HashMap::new();
+        for (id, (deadline, announcers)) in &mut self.missing {
+            if *deadline > now {
+                continue;
+            }
+            if let Some(node_id) = announcers.pop_front() {
+                grafts.entry(node_id).or_default().insert(*id);
+                *deadline = now + self.graft_timeout;
+            }
+        }
+        self.missing
+            .retain(|_, (_, announcers)| !announcers.is_empty());
+
+        for (node_id, ids) in grafts {
+            self.make_eager(&node_id);
+            let body = json!({"type": "plumtree_graft", "ids": Value::from(ids)});
+            ctx.send_reliable(node_id, body);
+        }
+    }
+
+    fn make_eager(&mut self, node_id: &str) {
+        if let Some(index) = self.lazy.iter().position(|id| id == node_id) {
+            self.eager.push(self.lazy.swap_remove(index));
+        }
+    }
+
+    fn make_lazy(&mut self, node_id: &str) {
+        if let Some(index) = self.eager.iter().position(|id| id == node_id) {
+            self.lazy.push(self.eager.swap_remove(index));
+        }
+    }
+}
+
+/// The messages handled by [Plumtree].
+pub enum PlumtreeMessage {
+    Gossip(Payloads),
+    IHave(Ids),
+    Graft(Ids),
+    Prune,
+    Tick,
+}
+
+impl TryFrom<Message> for PlumtreeMessage {
+    type Error = Error;
+
+    fn try_from(message: Message) -> Result<Self, Self::Error> {
+        let values = || Payloads::try_from(message.body()["values"].clone());
+        let ids = || Ids::try_from(message.body()["ids"].clone());
+        match message.msg_type() {
+            "plumtree_gossip" => values().map(PlumtreeMessage::Gossip),
+            "plumtree_ihave" => ids().map(PlumtreeMessage::IHave),
+            "plumtree_graft" => ids().map(PlumtreeMessage::Graft),
+            "plumtree_prune" => Ok(PlumtreeMessage::Prune),
+            "plumtree_tick" => Ok(PlumtreeMessage::Tick),
+            msg_type => Err(Error::not_supported(msg_type)),
+        }
+    }
+}
+
+/// Names a value in announcements and grafts: an integer by itself, and
+/// anything else by its [fingerprint].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Id {
+    Integer(u64),
+    Other(u64),
+}
+
+impl Id {
+    fn of(value: &Json) -> Self {
+        match value.value().as_u64() {
+            Some(integer) => Id::Integer(integer),
+            None => Id::Other(fingerprint(value)),
+        }
+    }
+}
+
+/// A set of [Id]s.
+///
+/// Encoded as `{"integers": ranges, "others": fingerprints}`, with the
+/// integers as an [IntervalSet].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Ids {
+    integers: IntervalSet,
+    others: BTreeSet<u64>,
+}
+
+impl Ids {
+    fn insert(&mut self, id: Id) {
+        match id {
+            Id::Integer(integer) => self.integers.insert(integer),
+            Id::Other(hash) => self.others.insert(hash),
+        };
+    }
+}
+
+impl TryFrom<Value> for Ids {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let others = value["others"]
+            .as_array()
+            .and_then(|others| others.iter().map(Value::as_u64).collect())
+            .ok_or_else(|| Error::malformed_request("expected an array of fingerprints"))?;
+        Ok(Self {
+            integers: IntervalSet::try_from(value["integers"].clone())?,
+            others,
+        })
+    }
+}
+
+impl From<Ids> for Value {
+    fn from(ids: Ids) -> Self {
+        json!({"integers": Value::from(ids.integers), "others": Vec::from_iter(ids.others)})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn announces_values_by_id() {
+        let values = [json!(3), json!(4), json!(9), json!({"a": [1, 2]})];
+        let mut ids = Ids::default();
+        for value in values {
+            ids.insert(Id::of(&Json::from(value)));
+        }
+        let id = |value| Id::of(&Json::from(value));
+        assert_eq!(id(json!({"a": 1, "b": 2})), id(json!({"b": 2, "a": 1})));
+        assert_ne!(id(json!({"a": 1, "b": 2})), id(json!({"a": 2, "b": 1})));
+
+        let value = Value::from(ids);
+        assert_eq!(value["integers"], json!([[3, 4], [9, 9]]));
+        assert_eq!(value["others"].as_array().unwrap().len(), 1);
+        let parsed = Ids::try_from(value.clone()).unwrap();
+        assert_eq!(Value::from(parsed), value);
+
+        assert!(Ids::try_from(json!({"integers": [], "others": ["a"]})).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+use crdt::Json;
+use maelstrom::{Context, Error};
+use serde_json::json;
+use std::time::Duration;
+use tokio::{spawn, task::JoinSet, time::timeout};
+
+/// How long to wait for a peer to acknowledge a value before asking again.
+const REPLICATE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Sends `value` to every other node, and replies `broadcast_ok` to the
+/// current message once `quorum` of them have acknowledged it with
+/// `replicate_ok`, or all of them if there are fewer. Unresponsive nodes
+/// are asked again until the quorum is reached or `deadline` passes, in
+/// which case the reply is `temporarily_unavailable`; the value may still
+/// reach every node through the configured replication.
+///
+/// The `replicate` requests go straight to each node rather than over the
+/// overlay, so every broadcast costs one request per other node, plus any
+/// retries, on top of what replication sends.
+pub fn replicate(value: Json, quorum: usize, deadline: Duration, ctx: Context) {
+    let peers = ctx
+        .node_ids()
+        .iter()
+        .filter(|id| *id != ctx.node_id())
+        .cloned()
+        .collect::<Vec<_>>();
+    let quorum = quorum.min(peers.len());
+
+    let body = json!({"type": "replicate", "message": value.value()});
+    let mut acks = JoinSet::new();
+    for peer in peers {
+        let (ctx, body) = (ctx.clone(), body.clone());
+        acks.spawn(async move {
+            loop {
+                match ctx.rpc(peer.clone(), body.clone(), REPLICATE_TIMEOUT).await {
+                    Err(error) if error.code() == Error::TIMEOUT => continue,
+                    result => return result.is_ok(),
+                }
+            }
+        });
+    }
+
+    spawn(async move {
+        let mut acked = 0;
+        let wait = async {
+            while acked < quorum {
+                match acks.join_next().await {
+                    Some(Ok(true)) => acked += 1,
+                    Some(_) => {}
+                    None => return false,
+                }
+            }
+            true
+        };
+        let reached = timeout(deadline, wait).await.unwrap_or(false);
+        // dropping the remaining requests stops their retries
+        drop(acks);
+
+        if reached {
+            ctx.reply(json!({"type": "broadcast_ok"}));
+        } else {
+            let text = format!("only {} peers acknowledged the value", acked);
+            ctx.reply(Error::temporarily_unavailable(&text));
+        }
+    });
+}
@@ -0,0 +1,27 @@
+use crdt::{GossipMessage, PNCounter};
+use maelstrom::{Error, Message};
+
+pub enum Command {
+    Add(i64),
+    Read,
+    Gossip(GossipMessage<PNCounter>),
+}
+
+impl TryFrom<Message> for Command {
+    type Error = Error;
+
+    fn try_from(value: Message) -> Result<Self, Self::Error> {
+        match value.msg_type() {
+            "add" => add(value),
+            "read" => Ok(Command::Read),
+            _ => GossipMessage::try_from(value).map(Command::Gossip),
+        }
+    }
+}
+
+fn add(message: Message) -> Result<Command, Error> {
+    match message.body()["delta"].as_i64() {
+        Some(delta) => Ok(Command::Add(delta)),
+        None => Err(Error::malformed_request("add message missing `delta` key")),
+    }
+}
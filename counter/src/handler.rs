@@ -0,0 +1,48 @@
+use crate::command::Command;
+use crdt::{Gossip, PNCounter};
+use maelstrom::{Context, Handler};
+use serde_json::json;
+
+/// A counter gossiped to every other node. It backs both the `g-counter`
+/// and `pn-counter` workloads: the former just never sends negative deltas.
+pub struct CounterHandler {
+    counter: Gossip<PNCounter>,
+}
+
+impl CounterHandler {
+    pub fn new() -> Self {
+        Self {
+            counter: Gossip::new(),
+        }
+    }
+
+    fn add(&mut self, delta: i64, ctx: Context) {
+        let node_id = ctx.node_id().to_string();
+        self.counter
+            .update(|counter| counter.add(&node_id, delta), &ctx);
+        ctx.reply(json!({"type": "add_ok"}));
+    }
+
+    fn read(&mut self, ctx: Context) {
+        let value = self.counter.state().value();
+        ctx.reply(json!({"type": "read_ok", "value": value}));
+    }
+}
+
+impl Handler for CounterHandler {
+    type Command = Command;
+
+    fn init(&mut self, ctx: Context) {
+        let peers = ctx.node_ids().iter().filter(|id| *id != ctx.node_id());
+        self.counter.set_neighbors(peers.cloned());
+        self.counter.init(&ctx);
+    }
+
+    fn handle(&mut self, command: Command, ctx: Context) {
+        match command {
+            Command::Add(delta) => self.add(delta, ctx),
+            Command::Read => self.read(ctx),
+            Command::Gossip(message) => self.counter.handle(message, &ctx),
+        }
+    }
+}
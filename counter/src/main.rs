@@ -0,0 +1,11 @@
+mod command;
+mod handler;
+
+use handler::CounterHandler;
+use maelstrom::Node;
+use tokio::task::JoinError;
+
+#[tokio::main]
+async fn main() -> Result<(), JoinError> {
+    Node::from_handler(CounterHandler::new()).start().await
+}
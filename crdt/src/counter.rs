@@ -0,0 +1,183 @@
+use crate::{element::invalid, Crdt};
+use maelstrom::Error;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+/// A grow-only counter: each node increments its own count, and the value is
+/// the sum of all counts.
+///
+/// Encoded as an object mapping node ids to counts.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GCounter {
+    counts: BTreeMap<String, u64>,
+}
+
+impl GCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn increment(&mut self, node_id: &str, delta: u64) {
+        *self.counts.entry(node_id.to_string()).or_default() += delta;
+    }
+
+    pub fn value(&self) -> u64 {
+        self.counts.values().sum()
+    }
+}
+
+impl Crdt for GCounter {
+    fn merge(&mut self, other: &Self) {
+        for (node_id, count) in &other.counts {
+            let local = self.counts.entry(node_id.clone()).or_default();
+            *local = (*local).max(*count);
+        }
+    }
+
+    fn delta(&self, since: &Self) -> Self {
+        let counts = self
+            .counts
+            .iter()
+            .filter(|(node_id, count)| since.counts.get(*node_id) < Some(count))
+            .map(|(node_id, count)| (node_id.clone(), *count))
+            .collect();
+        Self { counts }
+    }
+}
+
+impl TryFrom<Value> for GCounter {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let Value::Object(counts) = value else {
+            return Err(invalid("an object of counts", &value));
+        };
+
+        let counts = counts
+            .into_iter()
+            .map(|(node_id, count)| match count.as_u64() {
+                Some(count) => Ok((node_id, count)),
+                None => Err(invalid("a count", &count)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { counts })
+    }
+}
+
+impl From<GCounter> for Value {
+    fn from(counter: GCounter) -> Self {
+        let counts = counter.counts.into_iter().map(|(k, v)| (k, v.into()));
+        Value::Object(counts.collect::<Map<_, _>>())
+    }
+}
+
+/// A counter that can be incremented and decremented, made of two
+/// [GCounter]s: one for increments and one for decrements.
+///
+/// Encoded as `{"inc": counts, "dec": counts}`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PNCounter {
+    increments: GCounter,
+    decrements: GCounter,
+}
+
+impl PNCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `delta`, which may be negative, to the counter.
+    pub fn add(&mut self, node_id: &str, delta: i64) {
+        match delta >= 0 {
+            true => self.increments.increment(node_id, delta.unsigned_abs()),
+            false => self.decrements.increment(node_id, delta.unsigned_abs()),
+        }
+    }
+
+    pub fn value(&self) -> i64 {
+        self.increments.value() as i64 - self.decrements.value() as i64
+    }
+}
+
+impl Crdt for PNCounter {
+    fn merge(&mut self, other: &Self) {
+        self.increments.merge(&other.increments);
+        self.decrements.merge(&other.decrements);
+    }
+
+    fn delta(&self, since: &Self) -> Self {
+        Self {
+            increments: self.increments.delta(&since.increments),
+            decrements: self.decrements.delta(&since.decrements),
+        }
+    }
+}
+
+impl TryFrom<Value> for PNCounter {
+    type Error = Error;
+
+    fn try_from(mut value: Value) -> Result<Self, Self::Error> {
+        Ok(Self {
+            increments: GCounter::try_from(value["inc"].take())?,
+            decrements: GCounter::try_from(value["dec"].take())?,
+        })
+    }
+}
+
+impl From<PNCounter> for Value {
+    fn from(counter: PNCounter) -> Self {
+        json!({"inc": Value::from(counter.increments), "dec": Value::from(counter.decrements)})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laws;
+    use proptest::prelude::*;
+
+    const NODES: [&str; 3] = ["n0", "n1", "n2"];
+
+    fn g_counter() -> impl Strategy<Value = GCounter> {
+        prop::collection::vec((0..NODES.len(), 0..10u64), 0..8).prop_map(|increments| {
+            let mut counter = GCounter::new();
+            for (node, delta) in increments {
+                counter.increment(NODES[node], delta);
+            }
+            counter
+        })
+    }
+
+    fn pn_counter() -> impl Strategy<Value = PNCounter> {
+        prop::collection::vec((0..NODES.len(), -10..10i64), 0..8).prop_map(|deltas| {
+            let mut counter = PNCounter::new();
+            for (node, delta) in deltas {
+                counter.add(NODES[node], delta);
+            }
+            counter
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn g_counter_laws(a in g_counter(), b in g_counter(), c in g_counter()) {
+            laws::check(a, b, c);
+        }
+
+        #[test]
+        fn pn_counter_laws(a in pn_counter(), b in pn_counter(), c in pn_counter()) {
+            laws::check(a, b, c);
+        }
+    }
+
+    #[test]
+    fn merged_counts_add_up() {
+        let (mut a, mut b) = (PNCounter::new(), PNCounter::new());
+        a.add("n0", 5);
+        a.add("n0", -2);
+        b.add("n1", -4);
+
+        a.merge(&b);
+        assert_eq!(a.value(), -1);
+    }
+}
@@ -0,0 +1,194 @@
+use maelstrom::Error;
+use serde_json::Value;
+use std::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+};
+
+/// A value that can be stored in CRDT collections and registers: ordered,
+/// so states have a canonical form, and convertible to and from JSON.
+pub trait Element: Clone + Ord {
+    fn to_json(&self) -> Value;
+
+    fn from_json(value: &Value) -> Result<Self, Error>;
+}
+
+impl Element for u64 {
+    fn to_json(&self) -> Value {
+        Value::from(*self)
+    }
+
+    fn from_json(value: &Value) -> Result<Self, Error> {
+        value
+            .as_u64()
+            .ok_or_else(|| invalid("an unsigned integer", value))
+    }
+}
+
+impl Element for i64 {
+    fn to_json(&self) -> Value {
+        Value::from(*self)
+    }
+
+    fn from_json(value: &Value) -> Result<Self, Error> {
+        value.as_i64().ok_or_else(|| invalid("an integer", value))
+    }
+}
+
+impl Element for String {
+    fn to_json(&self) -> Value {
+        Value::from(self.as_str())
+    }
+
+    fn from_json(value: &Value) -> Result<Self, Error> {
+        value
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| invalid("a string", value))
+    }
+}
+
+/// `None` is encoded as `null`, so `T` must not use `null` itself.
+impl<T: Element> Element for Option<T> {
+    fn to_json(&self) -> Value {
+        self.as_ref().map_or(Value::Null, T::to_json)
+    }
+
+    fn from_json(value: &Value) -> Result<Self, Error> {
+        match value {
+            Value::Null => Ok(None),
+            value => T::from_json(value).map(Some),
+        }
+    }
+}
+
+/// Any JSON value, compared and ordered by its canonical encoding: compact,
+/// with object keys sorted. Values that differ only in key order are equal;
+/// numbers are compared as written, so `1` and `1.0` differ.
+#[derive(Clone, Debug)]
+pub struct Json {
+    value: Value,
+    canonical: String,
+}
+
+impl Json {
+    pub fn new(value: Value) -> Self {
+        let mut canonical = String::new();
+        encode(&value, &mut canonical);
+        Self { value, canonical }
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    pub fn canonical(&self) -> &str {
+        &self.canonical
+    }
+}
+
+impl From<Value> for Json {
+    fn from(value: Value) -> Self {
+        Self::new(value)
+    }
+}
+
+impl PartialEq for Json {
+    fn eq(&self, other: &Self) -> bool {
+        self.canonical == other.canonical
+    }
+}
+
+impl Eq for Json {}
+
+impl PartialOrd for Json {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Json {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.canonical.cmp(&other.canonical)
+    }
+}
+
+impl Hash for Json {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.canonical.hash(state);
+    }
+}
+
+impl Element for Json {
+    fn to_json(&self) -> Value {
+        self.value.clone()
+    }
+
+    fn from_json(value: &Value) -> Result<Self, Error> {
+        Ok(Self::new(value.clone()))
+    }
+}
+
+/// Appends the canonical encoding of `value` to `out`.
+fn encode(value: &Value, out: &mut String) {
+    match value {
+        Value::Array(values) => {
+            out.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                encode(value, out);
+            }
+            out.push(']');
+        }
+        Value::Object(fields) => {
+            let mut fields = fields.iter().collect::<Vec<_>>();
+            fields.sort_by_key(|(key, _)| *key);
+
+            out.push('{');
+            for (i, (key, value)) in fields.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::from(key.as_str()).to_string());
+                out.push(':');
+                encode(value, out);
+            }
+            out.push('}');
+        }
+        value => out.push_str(&value.to_string()),
+    }
+}
+
+pub(crate) fn invalid(expected: &str, value: &Value) -> Error {
+    Error::malformed_request(&format!("expected {}, got {}", expected, value))
+}
+
+/// Decodes a JSON array with `decode`.
+pub(crate) fn array<T>(
+    value: &Value,
+    decode: impl FnMut(&Value) -> Result<T, Error>,
+) -> Result<Vec<T>, Error> {
+    match value.as_array() {
+        Some(values) => values.iter().map(decode).collect(),
+        None => Err(invalid("an array", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn json_is_compared_by_canonical_encoding() {
+        let a = Json::new(json!({"b": [1, {"d": null, "c": "x"}], "a": true}));
+        let b = Json::from_json(&json!({"a": true, "b": [1, {"c": "x", "d": null}]})).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.canonical(), r#"{"a":true,"b":[1,{"c":"x","d":null}]}"#);
+
+        assert_ne!(Json::new(json!(1)), Json::new(json!(1.0)));
+        assert_ne!(Json::new(json!("1")), Json::new(json!(1)));
+    }
+}
@@ -0,0 +1,458 @@
+use crate::Crdt;
+use maelstrom::{Context, Error, Message};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+/// Replicates a [Crdt] to a set of neighbors by anti-entropy.
+///
+/// For each neighbor, the component tracks the part of the state the
+/// neighbor is known to have, and sends it the rest as a `gossip` message
+/// whenever the state changes, then again periodically until the neighbor
+/// acknowledges it with `gossip_ok`. Any state received from a node also
+/// counts as known to that node, so it is never sent back.
+///
+/// Resends to a neighbor back off exponentially while it doesn't answer,
+/// from the resend interval up to a maximum, and return to the resend
+/// interval as soon as it is heard from, e.g. once a partition heals.
+///
+/// With [batching](Gossip::with_batching), changes are held back and sent
+/// together once per flush interval, or as soon as enough of them have
+/// accumulated, trading latency for fewer messages.
+///
+/// The handler owns the component: it updates the state through
+/// [update](Gossip::update), calls [init](Gossip::init) when the node is
+/// initialized, and passes it every [GossipMessage] it receives.
+pub struct Gossip<S: Crdt> {
+    state: S,
+    peers: HashMap<String, Peer<S>>,
+    interval: Duration,
+    max_backoff: Duration,
+    last_id: u64,
+
+    /// how often held back changes are sent, and how many trigger a send
+    /// right away; `None` to send every change immediately
+    batching: Option<(Duration, usize)>,
+
+    /// changes since the state was last sent
+    unsent: usize,
+}
+
+/// What we know about a neighbor's copy of the state.
+struct Peer<S> {
+    /// the part of the state the neighbor is known to have
+    known: S,
+
+    /// the contents of the `gossip` messages sent but not acknowledged yet,
+    /// by id
+    in_flight: BTreeMap<u64, S>,
+
+    /// resends since the neighbor was last heard from
+    resends: u32,
+
+    /// when the neighbor is due for a resend
+    resend_at: Instant,
+}
+
+impl<S: Default> Peer<S> {
+    fn new() -> Self {
+        Self {
+            known: S::default(),
+            in_flight: BTreeMap::new(),
+            resends: 0,
+            resend_at: Instant::now(),
+        }
+    }
+
+    /// Resets the backoff, since the neighbor is reachable.
+    fn heard_from(&mut self) {
+        self.resends = 0;
+        self.resend_at = Instant::now();
+    }
+}
+
+impl<S: Crdt> Gossip<S> {
+    const DEFAULT_INTERVAL: Duration = Duration::from_millis(200);
+    const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+    pub fn new() -> Self {
+        Self {
+            state: S::default(),
+            peers: HashMap::new(),
+            interval: Self::DEFAULT_INTERVAL,
+            max_backoff: Self::DEFAULT_MAX_BACKOFF,
+            last_id: 0,
+            batching: None,
+            unsent: 0,
+        }
+    }
+
+    /// Returns a component that resends unacknowledged state once every
+    /// `interval`, backing off from there while a neighbor doesn't answer.
+    pub fn with_interval(self, interval: Duration) -> Self {
+        Self { interval, ..self }
+    }
+
+    /// Returns a component that waits at most `max_backoff` between resends
+    /// to a neighbor that doesn't answer.
+    pub fn with_max_backoff(self, max_backoff: Duration) -> Self {
+        Self {
+            max_backoff,
+            ..self
+        }
+    }
+
+    /// Returns a component that sends changes once every `flush_interval`,
+    /// or as soon as `max_batch` of them are waiting, instead of right away.
+    pub fn with_batching(self, flush_interval: Duration, max_batch: usize) -> Self {
+        let batching = Some((flush_interval, max_batch));
+        Self { batching, ..self }
+    }
+
+    /// The local state.
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Starts the periodic resends and flushes. Call this from
+    /// [Handler::init](maelstrom::Handler::init).
+    pub fn init(&self, ctx: &Context) {
+        ctx.notify_every(self.interval, json!({"type": "gossip_tick"}));
+        if let Some((flush_interval, _)) = self.batching {
+            ctx.notify_every(flush_interval, json!({"type": "gossip_flush"}));
+        }
+    }
+
+    /// Replaces the set of nodes the state is sent to. What is known about
+    /// nodes that remain neighbors is kept.
+    pub fn set_neighbors(&mut self, neighbors: impl IntoIterator<Item = String>) {
+        let mut peers = HashMap::new();
+        for node_id in neighbors {
+            let peer = self.peers.remove(&node_id).unwrap_or_else(Peer::new);
+            peers.insert(node_id, peer);
+        }
+        self.peers = peers;
+    }
+
+    /// Updates the local state, then sends the change to all neighbors,
+    /// possibly batched with others.
+    pub fn update(&mut self, update: impl FnOnce(&mut S), ctx: &Context) {
+        update(&mut self.state);
+        self.changed(ctx);
+    }
+
+    pub fn handle(&mut self, message: GossipMessage<S>, ctx: &Context) {
+        match message {
+            GossipMessage::Gossip { id, state } => {
+                let before = self.state.clone();
+                self.state.merge(&state);
+                let changed = self.state != before;
+
+                if let Some(peer) = self.peers.get_mut(ctx.src()) {
+                    peer.known.merge(&state);
+                    peer.heard_from();
+                }
+
+                ctx.reply(json!({"type": "gossip_ok", "id": id}));
+                if changed {
+                    self.changed(ctx);
+                }
+            }
+            GossipMessage::GossipOk { id } => {
+                let Some(peer) = self.peers.get_mut(ctx.src()) else {
+                    return;
+                };
+
+                if let Some(state) = peer.in_flight.remove(&id) {
+                    peer.known.merge(&state);
+
+                    // the neighbor is now known to have all earlier ones held
+                    peer.in_flight = peer.in_flight.split_off(&id);
+                }
+                peer.heard_from();
+            }
+            GossipMessage::Tick => self.send(ctx, true),
+            GossipMessage::Flush if self.unsent > 0 => self.send(ctx, false),
+            GossipMessage::Flush => {}
+        }
+    }
+
+    /// Sends a change now, or holds it back for the next batch.
+    fn changed(&mut self, ctx: &Context) {
+        self.unsent += 1;
+        match self.batching {
+            Some((_, max_batch)) if self.unsent < max_batch => {}
+            _ => self.send(ctx, false),
+        }
+    }
+
+    /// Sends each neighbor the part of the state it isn't known to have. On
+    /// `resend`, only neighbors that are due are sent to, and each resend
+    /// doubles the wait before the next one.
+    fn send(&mut self, ctx: &Context, resend: bool) {
+        let now = Instant::now();
+        if !resend {
+            self.unsent = 0;
+        }
+
+        for (node_id, peer) in self.peers.iter_mut() {
+            let delta = self.state.delta(&peer.known);
+            if delta == S::default() || (resend && now < peer.resend_at) {
+                continue;
+            }
+
+            if resend {
+                peer.resends += 1;
+            }
+            let backoff = self.interval.saturating_mul(1 << peer.resends.min(16));
+            peer.resend_at = now + backoff.min(self.max_backoff);
+
+            self.last_id += 1;
+            let state: Value = delta.clone().into();
+            let body = json!({"type": "gossip", "id": self.last_id, "state": state});
+            ctx.send(node_id.to_string(), None, body);
+            peer.in_flight.insert(self.last_id, delta);
+        }
+    }
+}
+
+impl<S: Crdt> Default for Gossip<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The messages handled by [Gossip].
+pub enum GossipMessage<S> {
+    Gossip { id: u64, state: S },
+    GossipOk { id: u64 },
+    Tick,
+    Flush,
+}
+
+impl<S: Crdt> TryFrom<Message> for GossipMessage<S> {
+    type Error = Error;
+
+    fn try_from(message: Message) -> Result<Self, Self::Error> {
+        let body = message.body();
+        match (message.msg_type(), body["id"].as_u64()) {
+            ("gossip", Some(id)) => {
+                let state = S::try_from(body["state"].clone())?;
+                Ok(GossipMessage::Gossip { id, state })
+            }
+            ("gossip_ok", Some(id)) => Ok(GossipMessage::GossipOk { id }),
+            ("gossip_tick", _) => Ok(GossipMessage::Tick),
+            ("gossip_flush", _) => Ok(GossipMessage::Flush),
+            ("gossip" | "gossip_ok", None) => {
+                Err(Error::malformed_request("gossip message missing `id` key"))
+            }
+            (msg_type, _) => Err(Error::not_supported(msg_type)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GSet;
+    use maelstrom::{testing::Network, Handler};
+    use std::sync::{Arc, Mutex};
+    use tokio::time::sleep;
+
+    enum Command {
+        Add(u64),
+        Read,
+        Gossip(GossipMessage<GSet<u64>>),
+    }
+
+    impl TryFrom<Message> for Command {
+        type Error = Error;
+
+        fn try_from(message: Message) -> Result<Self, Self::Error> {
+            match message.msg_type() {
+                "add" => Ok(Command::Add(message.body()["element"].as_u64().unwrap())),
+                "read" => Ok(Command::Read),
+                _ => GossipMessage::try_from(message).map(Command::Gossip),
+            }
+        }
+    }
+
+    /// A g-set replicated along the line n0 - n1 - n2.
+    struct Line(Gossip<GSet<u64>>);
+
+    impl Handler for Line {
+        type Command = Command;
+
+        fn init(&mut self, ctx: Context) {
+            let neighbors = match ctx.node_id() {
+                "n1" => vec!["n0", "n2"],
+                _ => vec!["n1"],
+            };
+            self.0
+                .set_neighbors(neighbors.into_iter().map(String::from));
+            self.0.init(&ctx);
+        }
+
+        fn handle(&mut self, command: Self::Command, ctx: Context) {
+            match command {
+                Command::Add(element) => {
+                    self.0.update(|set| _ = set.insert(element), &ctx);
+                    ctx.reply(json!({"type": "add_ok"}));
+                }
+                Command::Read => {
+                    let value: Value = self.0.state().clone().into();
+                    ctx.reply(json!({"type": "read_ok", "value": value}));
+                }
+                Command::Gossip(message) => self.0.handle(message, &ctx),
+            }
+        }
+    }
+
+    /// A neighbor that records the size of each `gossip` message it gets,
+    /// and acknowledges each one only once the next one arrives.
+    struct Lagging {
+        sizes: Arc<Mutex<Vec<usize>>>,
+        unacked: Option<(u64, Context)>,
+    }
+
+    impl Handler for Lagging {
+        type Command = Command;
+
+        fn handle(&mut self, command: Self::Command, ctx: Context) {
+            if let Command::Gossip(GossipMessage::Gossip { id, state }) = command {
+                self.sizes.lock().unwrap().push(state.len());
+                if let Some((id, ctx)) = self.unacked.replace((id, ctx)) {
+                    ctx.reply(json!({"type": "gossip_ok", "id": id}));
+                }
+            }
+        }
+    }
+
+    const NODES: [&str; 3] = ["n0", "n1", "n2"];
+
+    async fn converge(network: &Network, expected: Value) {
+        for _ in 0..100 {
+            let mut values = vec![];
+            for node_id in NODES {
+                let reply = network.request(node_id, json!({"type": "read"})).await;
+                values.push(reply["value"].clone());
+            }
+
+            if values.iter().all(|value| *value == expected) {
+                return;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+
+        panic!("nodes did not converge to {}", expected);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn replicates_through_neighbors() {
+        let network = Network::new();
+        for node_id in NODES {
+            network.start(node_id, Line(Gossip::new()));
+        }
+        network.init(&NODES).await;
+
+        network
+            .request("n0", json!({"type": "add", "element": 1}))
+            .await;
+        network
+            .request("n2", json!({"type": "add", "element": 2}))
+            .await;
+
+        converge(&network, json!([1, 2])).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn resends_after_partition() {
+        let network = Network::new();
+        for node_id in NODES {
+            let gossip = Gossip::new()
+                .with_interval(Duration::from_millis(20))
+                .with_max_backoff(Duration::from_millis(100));
+            network.start(node_id, Line(gossip));
+        }
+        network.init(&NODES).await;
+
+        network.isolate("n1");
+        network
+            .request("n0", json!({"type": "add", "element": 1}))
+            .await;
+        network
+            .request("n2", json!({"type": "add", "element": 2}))
+            .await;
+        // long enough for resends to back off to the maximum
+        sleep(Duration::from_millis(300)).await;
+
+        let reply = network.request("n1", json!({"type": "read"})).await;
+        assert_eq!(reply["value"], json!([]));
+
+        network.heal();
+        converge(&network, json!([1, 2])).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn batches_changes() {
+        let network = Network::new();
+        for node_id in NODES {
+            let gossip = Gossip::new()
+                .with_interval(Duration::from_secs(10))
+                .with_batching(Duration::from_millis(300), 3);
+            network.start(node_id, Line(gossip));
+        }
+        network.init(&NODES).await;
+
+        for element in [1, 2] {
+            let body = json!({"type": "add", "element": element});
+            network.request("n0", body).await;
+        }
+        sleep(Duration::from_millis(100)).await;
+        let reply = network.request("n1", json!({"type": "read"})).await;
+        assert_eq!(reply["value"], json!([]));
+
+        // the third change fills the batch
+        network
+            .request("n0", json!({"type": "add", "element": 3}))
+            .await;
+        sleep(Duration::from_millis(100)).await;
+        let reply = network.request("n1", json!({"type": "read"})).await;
+        assert_eq!(reply["value"], json!([1, 2, 3]));
+
+        // and a lone change waits for the flush
+        network
+            .request("n2", json!({"type": "add", "element": 4}))
+            .await;
+        converge(&network, json!([1, 2, 3, 4])).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn acknowledgements_shrink_later_messages() {
+        let network = Network::new();
+        let sizes = Arc::new(Mutex::new(vec![]));
+        let gossip = Gossip::new().with_interval(Duration::from_secs(10));
+        network.start("n0", Line(gossip));
+        let lagging = Lagging {
+            sizes: sizes.clone(),
+            unacked: None,
+        };
+        network.start("n1", lagging);
+        network.init(&["n0", "n1"]).await;
+
+        for element in 0..20 {
+            let body = json!({"type": "add", "element": element});
+            network.request("n0", body).await;
+        }
+        sleep(Duration::from_millis(100)).await;
+
+        // every message is acknowledged only after a later one was sent,
+        // and still counts towards what n1 is known to have, so each message
+        // holds just the few changes since then instead of all of them
+        let sizes = sizes.lock().unwrap();
+        assert_eq!(sizes.len(), 20);
+        assert!(sizes.iter().all(|size| *size <= 5), "sent {:?}", sizes);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{
+    element::{array, invalid},
+    Crdt,
+};
+use maelstrom::Error;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// A grow-only set of integers stored as disjoint ranges, compact for sets
+/// of mostly consecutive values.
+///
+/// Ranges are kept maximal: overlapping or adjacent ranges are coalesced,
+/// so equal sets have equal representations. Encoded as an array of
+/// inclusive `[start, end]` pairs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IntervalSet {
+    /// inclusive ranges, end by start
+    ranges: BTreeMap<u64, u64>,
+}
+
+impl IntervalSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a value, returning whether it is new.
+    pub fn insert(&mut self, value: u64) -> bool {
+        if self.contains(value) {
+            return false;
+        }
+
+        self.insert_range(value, value);
+        true
+    }
+
+    /// Adds all values from `start` to `end`, inclusive.
+    pub fn insert_range(&mut self, mut start: u64, mut end: u64) {
+        if let Some((&before, &before_end)) = self.ranges.range(..=start).next_back() {
+            if before_end.saturating_add(1) >= start {
+                start = before;
+                end = end.max(before_end);
+            }
+        }
+
+        // `start` may have moved back to a range that is absorbed below
+        while let Some((&next, &next_end)) = self.ranges.range(start..).next() {
+            if next > end.saturating_add(1) {
+                break;
+            }
+            self.ranges.remove(&next);
+            end = end.max(next_end);
+        }
+
+        self.ranges.insert(start, end);
+    }
+
+    pub fn contains(&self, value: u64) -> bool {
+        self.ranges
+            .range(..=value)
+            .next_back()
+            .is_some_and(|(_, end)| value <= *end)
+    }
+
+    /// The number of values in the set.
+    pub fn len(&self) -> u64 {
+        self.ranges.iter().map(|(start, end)| end - start + 1).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// The values in the set, in order.
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.ranges.iter().flat_map(|(start, end)| *start..=*end)
+    }
+
+    /// The inclusive ranges of values in the set, in order.
+    pub fn ranges(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.ranges.iter().map(|(start, end)| (*start, *end))
+    }
+
+    /// The inclusive ranges of values in the set from `start` to `end`,
+    /// clipped to them, in order.
+    pub fn ranges_within(&self, start: u64, end: u64) -> impl Iterator<Item = (u64, u64)> + '_ {
+        // the first range that may overlap `start`
+        let from = self
+            .ranges
+            .range(..=start)
+            .next_back()
+            .map_or(start, |(from, _)| *from);
+
+        self.ranges
+            .range(from..=end.max(from))
+            .filter(move |(_, range_end)| **range_end >= start)
+            .map(move |(range_start, range_end)| (*range_start.max(&start), *range_end.min(&end)))
+    }
+}
+
+impl FromIterator<u64> for IntervalSet {
+    fn from_iter<I: IntoIterator<Item = u64>>(iter: I) -> Self {
+        let mut set = Self::new();
+        for value in iter {
+            set.insert(value);
+        }
+        set
+    }
+}
+
+impl Crdt for IntervalSet {
+    fn merge(&mut self, other: &Self) {
+        for (start, end) in other.ranges() {
+            self.insert_range(start, end);
+        }
+    }
+
+    fn delta(&self, since: &Self) -> Self {
+        let mut delta = Self::new();
+        for (start, end) in self.ranges() {
+            // the first range of `since` that may overlap this one
+            let from = since
+                .ranges
+                .range(..=start)
+                .next_back()
+                .map_or(start, |(from, _)| *from);
+
+            let mut next = Some(start);
+            for (&covered, &covered_end) in since.ranges.range(from..=end) {
+                let Some(uncovered) = next.filter(|uncovered| *uncovered <= end) else {
+                    break;
+                };
+                if covered > uncovered {
+                    delta.ranges.insert(uncovered, covered - 1);
+                }
+                if covered_end >= uncovered {
+                    next = covered_end.checked_add(1);
+                }
+            }
+
+            if let Some(uncovered) = next.filter(|uncovered| *uncovered <= end) {
+                delta.ranges.insert(uncovered, end);
+            }
+        }
+        delta
+    }
+}
+
+impl TryFrom<Value> for IntervalSet {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let ranges = array(&value, |range| {
+            match (range[0].as_u64(), range[1].as_u64()) {
+                (Some(start), Some(end)) if start <= end => Ok((start, end)),
+                _ => Err(invalid("a [start, end] range", range)),
+            }
+        })?;
+
+        let mut set = Self::new();
+        for (start, end) in ranges {
+            set.insert_range(start, end);
+        }
+        Ok(set)
+    }
+}
+
+impl From<IntervalSet> for Value {
+    fn from(set: IntervalSet) -> Self {
+        set.ranges()
+            .map(|(start, end)| json!([start, end]))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laws;
+    use proptest::prelude::*;
+
+    fn interval_set() -> impl Strategy<Value = IntervalSet> {
+        prop::collection::vec((0..40u64, 0..5u64), 0..8).prop_map(|ranges| {
+            let mut set = IntervalSet::new();
+            for (start, length) in ranges {
+                set.insert_range(start, start + length);
+            }
+            set
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn interval_set_laws(a in interval_set(), b in interval_set(), c in interval_set()) {
+            laws::check(a, b, c);
+        }
+
+        #[test]
+        fn matches_a_set_of_values(values in prop::collection::btree_set(0..100u64, 0..40)) {
+            let set = values.iter().copied().collect::<IntervalSet>();
+            prop_assert_eq!(set.iter().collect::<Vec<_>>(), values.iter().copied().collect::<Vec<_>>());
+            prop_assert_eq!(set.len(), values.len() as u64);
+        }
+
+        #[test]
+        fn clips_ranges(set in interval_set(), start in 0..50u64, length in 0..20u64) {
+            let end = start + length;
+            let within = set
+                .ranges_within(start, end)
+                .flat_map(|(start, end)| start..=end)
+                .collect::<Vec<_>>();
+            let expected = set.iter().filter(|value| (start..=end).contains(value));
+            prop_assert_eq!(within, expected.collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn coalesces_ranges() {
+        let mut set = IntervalSet::from_iter([1, 2, 3, 7, 5]);
+        assert_eq!(Value::from(set.clone()), json!([[1, 3], [5, 5], [7, 7]]));
+
+        assert!(set.insert(6));
+        assert!(!set.insert(2));
+        assert_eq!(Value::from(set.clone()), json!([[1, 3], [5, 7]]));
+
+        let since = IntervalSet::from_iter([2, 6]);
+        assert_eq!(
+            Value::from(set.delta(&since)),
+            json!([[1, 1], [3, 3], [5, 5], [7, 7]])
+        );
+    }
+}
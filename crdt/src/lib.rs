@@ -0,0 +1,78 @@
+//! State-based CRDTs ([convergent replicated data types][crdt]) for
+//! [Maelstrom] nodes.
+//!
+//! Every type implements [Crdt]: replicas update their own copy locally and
+//! exchange states (or [deltas](Crdt::delta)) in message bodies, and merging
+//! them in any order, any number of times, converges to the same state.
+//! States convert to and from [serde_json::Value] so they can be embedded in
+//! Maelstrom bodies as is, and [Gossip] replicates any of them between
+//! nodes.
+//!
+//! [crdt]: https://inria.hal.science/inria-00555588/document
+//! [Maelstrom]: https://github.com/jepsen-io/maelstrom/tree/main
+mod counter;
+mod element;
+mod gossip;
+mod interval_set;
+mod lww;
+mod or_set;
+mod set;
+
+pub use counter::*;
+pub use element::*;
+pub use gossip::*;
+pub use interval_set::*;
+pub use lww::*;
+pub use or_set::*;
+pub use set::*;
+
+use maelstrom::Error;
+use serde_json::Value;
+
+/// A state-based CRDT.
+///
+/// [merge](Crdt::merge) must be commutative, associative and idempotent, so
+/// replicas that have merged the same states are equal regardless of the
+/// order they were merged in or how many times.
+pub trait Crdt: Clone + Default + PartialEq + TryFrom<Value, Error = Error> + Into<Value> {
+    /// Merges `other` into `self`, leaving `self` as the least upper bound of
+    /// both states.
+    fn merge(&mut self, other: &Self);
+
+    /// The part of `self` that `since` may be missing: merging it into
+    /// `since` has the same effect as merging all of `self`. Replicas use
+    /// this to send only what a peer hasn't acknowledged yet.
+    fn delta(&self, since: &Self) -> Self;
+}
+
+#[cfg(test)]
+mod laws {
+    use super::Crdt;
+    use serde_json::Value;
+
+    /// Checks the semilattice laws, the delta law and the JSON round trip on
+    /// three arbitrary states.
+    pub fn check<T: Crdt + std::fmt::Debug>(a: T, b: T, c: T) {
+        let merged = |x: &T, y: &T| {
+            let mut x = x.clone();
+            x.merge(y);
+            x
+        };
+
+        assert_eq!(merged(&a, &b), merged(&b, &a), "merge is not commutative");
+        assert_eq!(
+            merged(&merged(&a, &b), &c),
+            merged(&a, &merged(&b, &c)),
+            "merge is not associative"
+        );
+        assert_eq!(merged(&a, &a), a, "merge is not idempotent");
+        assert_eq!(
+            merged(&b, &a.delta(&b)),
+            merged(&b, &a),
+            "delta is missing updates"
+        );
+
+        let json: Value = a.clone().into();
+        assert_eq!(T::try_from(json).ok(), Some(a), "JSON round trip failed");
+    }
+}
@@ -0,0 +1,264 @@
+use crate::{
+    element::{array, invalid},
+    Crdt, Element,
+};
+use maelstrom::Error;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// When a [LwwRegister] was written: a timestamp chosen by the writer, with
+/// ties broken by the writer's node id.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Stamp {
+    pub timestamp: u64,
+    pub node_id: String,
+}
+
+impl Stamp {
+    pub fn new(timestamp: u64, node_id: &str) -> Self {
+        Self {
+            timestamp,
+            node_id: node_id.to_string(),
+        }
+    }
+}
+
+/// A last-writer-wins register: merging keeps the value with the greatest
+/// [Stamp]. Writes with equal stamps are ordered by value, so merging stays
+/// deterministic even if a writer reuses a stamp.
+///
+/// Encoded as `{"value": value, "timestamp": timestamp, "node_id": node_id}`,
+/// or `null` if never written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LwwRegister<T: Element> {
+    write: Option<(Stamp, T)>,
+}
+
+impl<T: Element> LwwRegister<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes `value` unless the register already holds a later write.
+    pub fn set(&mut self, stamp: Stamp, value: T) {
+        let write = Some((stamp, value));
+        if write > self.write {
+            self.write = write;
+        }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        self.write.as_ref().map(|(_, value)| value)
+    }
+
+    pub fn stamp(&self) -> Option<&Stamp> {
+        self.write.as_ref().map(|(stamp, _)| stamp)
+    }
+}
+
+impl<T: Element> Default for LwwRegister<T> {
+    fn default() -> Self {
+        Self { write: None }
+    }
+}
+
+impl<T: Element> Crdt for LwwRegister<T> {
+    fn merge(&mut self, other: &Self) {
+        if other.write > self.write {
+            self.write = other.write.clone();
+        }
+    }
+
+    fn delta(&self, since: &Self) -> Self {
+        match self.write > since.write {
+            true => self.clone(),
+            false => Self::default(),
+        }
+    }
+}
+
+impl<T: Element> TryFrom<Value> for LwwRegister<T> {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        if value.is_null() {
+            return Ok(Self::default());
+        }
+
+        match (value["timestamp"].as_u64(), value["node_id"].as_str()) {
+            (Some(timestamp), Some(node_id)) => {
+                let stamp = Stamp::new(timestamp, node_id);
+                let value = T::from_json(&value["value"])?;
+                Ok(Self {
+                    write: Some((stamp, value)),
+                })
+            }
+            _ => Err(invalid("a register", &value)),
+        }
+    }
+}
+
+impl<T: Element> From<LwwRegister<T>> for Value {
+    fn from(register: LwwRegister<T>) -> Self {
+        match register.write {
+            Some((stamp, value)) => json!({
+                "value": value.to_json(),
+                "timestamp": stamp.timestamp,
+                "node_id": stamp.node_id,
+            }),
+            None => Value::Null,
+        }
+    }
+}
+
+/// A map of [LwwRegister]s. Removing a key writes a tombstone to its
+/// register, so a removal and a write to the same key are ordered by their
+/// stamps like any two writes.
+///
+/// Encoded as an array of `[key, register]` pairs, where removed keys'
+/// registers hold `null`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LwwMap<K: Element, V: Element> {
+    entries: BTreeMap<K, LwwRegister<Option<V>>>,
+}
+
+impl<K: Element, V: Element> LwwMap<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, stamp: Stamp, key: K, value: V) {
+        self.entries.entry(key).or_default().set(stamp, Some(value));
+    }
+
+    pub fn remove(&mut self, stamp: Stamp, key: K) {
+        self.entries.entry(key).or_default().set(stamp, None);
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.entries.get(key)?.get()?.as_ref()
+    }
+
+    /// The keys and values currently in the map.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries
+            .iter()
+            .filter_map(|(key, register)| Some((key, register.get()?.as_ref()?)))
+    }
+}
+
+impl<K: Element, V: Element> Default for LwwMap<K, V> {
+    fn default() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+impl<K: Element, V: Element> Crdt for LwwMap<K, V> {
+    fn merge(&mut self, other: &Self) {
+        for (key, register) in &other.entries {
+            self.entries.entry(key.clone()).or_default().merge(register);
+        }
+    }
+
+    fn delta(&self, since: &Self) -> Self {
+        let empty = LwwRegister::default();
+        let entries = self
+            .entries
+            .iter()
+            .map(|(key, register)| {
+                let seen = since.entries.get(key).unwrap_or(&empty);
+                (key.clone(), register.delta(seen))
+            })
+            .filter(|(_, register)| register.write.is_some())
+            .collect();
+        Self { entries }
+    }
+}
+
+impl<K: Element, V: Element> TryFrom<Value> for LwwMap<K, V> {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let entries = array(&value, |entry| {
+            let key = K::from_json(&entry[0])?;
+            Ok((key, LwwRegister::try_from(entry[1].clone())?))
+        })?;
+        Ok(Self {
+            entries: entries.into_iter().collect(),
+        })
+    }
+}
+
+impl<K: Element, V: Element> From<LwwMap<K, V>> for Value {
+    fn from(map: LwwMap<K, V>) -> Self {
+        map.entries
+            .into_iter()
+            .map(|(key, register)| json!([key.to_json(), Value::from(register)]))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laws;
+    use proptest::prelude::*;
+
+    const NODES: [&str; 3] = ["n0", "n1", "n2"];
+
+    fn stamp() -> impl Strategy<Value = Stamp> {
+        (0..5u64, 0..NODES.len()).prop_map(|(timestamp, node)| Stamp::new(timestamp, NODES[node]))
+    }
+
+    fn register() -> impl Strategy<Value = LwwRegister<String>> {
+        prop::collection::vec((stamp(), "[a-c]"), 0..4).prop_map(|writes| {
+            let mut register = LwwRegister::new();
+            for (stamp, value) in writes {
+                register.set(stamp, value);
+            }
+            register
+        })
+    }
+
+    fn map() -> impl Strategy<Value = LwwMap<u64, String>> {
+        let op = (stamp(), 0..5u64, prop::option::of("[a-c]"));
+        prop::collection::vec(op, 0..8).prop_map(|ops| {
+            let mut map = LwwMap::new();
+            for (stamp, key, value) in ops {
+                match value {
+                    Some(value) => map.insert(stamp, key, value),
+                    None => map.remove(stamp, key),
+                }
+            }
+            map
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn register_laws(a in register(), b in register(), c in register()) {
+            laws::check(a, b, c);
+        }
+
+        #[test]
+        fn map_laws(a in map(), b in map(), c in map()) {
+            laws::check(a, b, c);
+        }
+    }
+
+    #[test]
+    fn later_writes_win() {
+        let (mut a, mut b) = (LwwMap::<u64, u64>::new(), LwwMap::new());
+        a.insert(Stamp::new(1, "n0"), 1, 10);
+        b.remove(Stamp::new(2, "n1"), 1);
+        a.insert(Stamp::new(1, "n0"), 2, 20);
+        b.insert(Stamp::new(1, "n1"), 2, 21);
+
+        a.merge(&b);
+        assert_eq!(a.get(&1), None);
+        assert_eq!(a.get(&2), Some(&21));
+        assert_eq!(a.iter().count(), 1);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{
+    element::{array, invalid},
+    Crdt, Element,
+};
+use maelstrom::Error;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A unique tag for an addition to an [ORSet]: the id of the node that made
+/// it, and a per-node sequence number.
+type Tag = (String, u64);
+
+/// An observed-remove set: an element can be added and removed any number
+/// of times, and a removal only cancels the additions it has observed, so
+/// concurrent additions win.
+///
+/// Each addition is tagged uniquely; removing an element moves the tags seen
+/// for it to a set of tombstones. Encoded as
+/// `{"entries": [[element, [[node_id, seq], ...]], ...], "tombstones": [[node_id, seq], ...]}`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ORSet<T: Element> {
+    entries: BTreeMap<T, BTreeSet<Tag>>,
+    tombstones: BTreeSet<Tag>,
+}
+
+impl<T: Element> ORSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, node_id: &str, element: T) {
+        let seq = self
+            .tags()
+            .filter(|(id, _)| id == node_id)
+            .map(|(_, seq)| *seq)
+            .max()
+            .unwrap_or_default();
+
+        let tag = (node_id.to_string(), seq + 1);
+        self.entries.entry(element).or_default().insert(tag);
+    }
+
+    /// Removes an element, cancelling all the additions of it seen so far.
+    pub fn remove(&mut self, element: &T) {
+        if let Some(tags) = self.entries.remove(element) {
+            self.tombstones.extend(tags);
+        }
+    }
+
+    pub fn contains(&self, element: &T) -> bool {
+        self.entries.contains_key(element)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.entries.keys()
+    }
+
+    fn tags(&self) -> impl Iterator<Item = &Tag> {
+        self.entries.values().flatten().chain(&self.tombstones)
+    }
+}
+
+impl<T: Element> Default for ORSet<T> {
+    fn default() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            tombstones: BTreeSet::new(),
+        }
+    }
+}
+
+impl<T: Element> Crdt for ORSet<T> {
+    fn merge(&mut self, other: &Self) {
+        self.tombstones.extend(other.tombstones.iter().cloned());
+
+        for (element, tags) in &other.entries {
+            self.entries
+                .entry(element.clone())
+                .or_default()
+                .extend(tags.iter().cloned());
+        }
+
+        for tags in self.entries.values_mut() {
+            tags.retain(|tag| !self.tombstones.contains(tag));
+        }
+        self.entries.retain(|_, tags| !tags.is_empty());
+    }
+
+    fn delta(&self, since: &Self) -> Self {
+        let empty = BTreeSet::new();
+        let entries = self
+            .entries
+            .iter()
+            .map(|(element, tags)| {
+                let seen = since.entries.get(element).unwrap_or(&empty);
+                (element.clone(), tags.difference(seen).cloned().collect())
+            })
+            .filter(|(_, tags): &(T, BTreeSet<Tag>)| !tags.is_empty())
+            .collect();
+        let tombstones = self
+            .tombstones
+            .difference(&since.tombstones)
+            .cloned()
+            .collect();
+
+        Self {
+            entries,
+            tombstones,
+        }
+    }
+}
+
+impl<T: Element> TryFrom<Value> for ORSet<T> {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let entries = array(&value["entries"], |entry| {
+            let tags = array(&entry[1], tag)?;
+            Ok((T::from_json(&entry[0])?, tags.into_iter().collect()))
+        })?;
+        let tombstones = array(&value["tombstones"], tag)?;
+
+        Ok(Self {
+            entries: entries.into_iter().collect(),
+            tombstones: tombstones.into_iter().collect(),
+        })
+    }
+}
+
+impl<T: Element> From<ORSet<T>> for Value {
+    fn from(set: ORSet<T>) -> Self {
+        let encode = |tags: &BTreeSet<Tag>| tags.iter().map(|tag| json!(tag)).collect::<Value>();
+        let entries = set
+            .entries
+            .iter()
+            .map(|(element, tags)| json!([element.to_json(), encode(tags)]))
+            .collect::<Value>();
+
+        json!({"entries": entries, "tombstones": encode(&set.tombstones)})
+    }
+}
+
+fn tag(value: &Value) -> Result<Tag, Error> {
+    match (value[0].as_str(), value[1].as_u64()) {
+        (Some(node_id), Some(seq)) => Ok((node_id.to_string(), seq)),
+        _ => Err(invalid("a [node_id, seq] tag", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laws;
+    use proptest::prelude::*;
+
+    const NODES: [&str; 3] = ["n0", "n1", "n2"];
+
+    /// An OR-Set built by a single node; states from different nodes can be
+    /// merged, while two states from the same node would reuse tags.
+    fn or_set(node_id: &'static str) -> impl Strategy<Value = ORSet<u64>> {
+        prop::collection::vec((any::<bool>(), 0..10u64), 0..10).prop_map(move |ops| {
+            let mut set = ORSet::new();
+            for (insert, element) in ops {
+                match insert {
+                    true => set.insert(node_id, element),
+                    false => set.remove(&element),
+                }
+            }
+            set
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn or_set_laws(a in or_set(NODES[0]), b in or_set(NODES[1]), c in or_set(NODES[2])) {
+            laws::check(a, b, c);
+        }
+    }
+
+    #[test]
+    fn concurrent_add_wins() {
+        let mut a = ORSet::<u64>::new();
+        a.insert("n0", 1);
+
+        let mut b = a.clone();
+        a.remove(&1);
+        b.insert("n1", 1);
+
+        a.merge(&b);
+        assert!(a.contains(&1));
+
+        a.remove(&1);
+        b.merge(&a);
+        assert!(!b.contains(&1));
+    }
+}
@@ -0,0 +1,201 @@
+use crate::{element::array, Crdt, Element};
+use maelstrom::Error;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+
+/// A grow-only set.
+///
+/// Encoded as an array of elements.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GSet<T: Element> {
+    elements: BTreeSet<T>,
+}
+
+impl<T: Element> GSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an element, returning whether it is new.
+    pub fn insert(&mut self, element: T) -> bool {
+        self.elements.insert(element)
+    }
+
+    pub fn contains(&self, element: &T) -> bool {
+        self.elements.contains(element)
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.elements.iter()
+    }
+}
+
+impl<T: Element> Default for GSet<T> {
+    fn default() -> Self {
+        Self {
+            elements: BTreeSet::new(),
+        }
+    }
+}
+
+impl<T: Element> FromIterator<T> for GSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self {
+            elements: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<T: Element> Crdt for GSet<T> {
+    fn merge(&mut self, other: &Self) {
+        self.elements.extend(other.elements.iter().cloned());
+    }
+
+    fn delta(&self, since: &Self) -> Self {
+        self.elements.difference(&since.elements).cloned().collect()
+    }
+}
+
+impl<T: Element> TryFrom<Value> for GSet<T> {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let elements = array(&value, T::from_json)?;
+        Ok(elements.into_iter().collect())
+    }
+}
+
+impl<T: Element> From<GSet<T>> for Value {
+    fn from(set: GSet<T>) -> Self {
+        set.elements.iter().map(T::to_json).collect()
+    }
+}
+
+/// A two-phase set: elements can be added and then removed, but never added
+/// again once removed.
+///
+/// Encoded as `{"added": elements, "removed": elements}`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TwoPSet<T: Element> {
+    added: GSet<T>,
+    removed: GSet<T>,
+}
+
+impl<T: Element> TwoPSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, element: T) {
+        self.added.insert(element);
+    }
+
+    /// Removes an element for good. Removing an element that hasn't been
+    /// added yet prevents it from ever being added.
+    pub fn remove(&mut self, element: T) {
+        self.removed.insert(element);
+    }
+
+    pub fn contains(&self, element: &T) -> bool {
+        self.added.contains(element) && !self.removed.contains(element)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.added.iter().filter(|e| !self.removed.contains(e))
+    }
+}
+
+impl<T: Element> Default for TwoPSet<T> {
+    fn default() -> Self {
+        Self {
+            added: GSet::default(),
+            removed: GSet::default(),
+        }
+    }
+}
+
+impl<T: Element> Crdt for TwoPSet<T> {
+    fn merge(&mut self, other: &Self) {
+        self.added.merge(&other.added);
+        self.removed.merge(&other.removed);
+    }
+
+    fn delta(&self, since: &Self) -> Self {
+        Self {
+            added: self.added.delta(&since.added),
+            removed: self.removed.delta(&since.removed),
+        }
+    }
+}
+
+impl<T: Element> TryFrom<Value> for TwoPSet<T> {
+    type Error = Error;
+
+    fn try_from(mut value: Value) -> Result<Self, Self::Error> {
+        Ok(Self {
+            added: GSet::try_from(value["added"].take())?,
+            removed: GSet::try_from(value["removed"].take())?,
+        })
+    }
+}
+
+impl<T: Element> From<TwoPSet<T>> for Value {
+    fn from(set: TwoPSet<T>) -> Self {
+        json!({"added": Value::from(set.added), "removed": Value::from(set.removed)})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laws;
+    use proptest::prelude::*;
+
+    fn g_set() -> impl Strategy<Value = GSet<u64>> {
+        prop::collection::vec(0..20u64, 0..10).prop_map(GSet::from_iter)
+    }
+
+    fn two_p_set() -> impl Strategy<Value = TwoPSet<u64>> {
+        prop::collection::vec((any::<bool>(), 0..20u64), 0..10).prop_map(|ops| {
+            let mut set = TwoPSet::new();
+            for (insert, element) in ops {
+                match insert {
+                    true => set.insert(element),
+                    false => set.remove(element),
+                }
+            }
+            set
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn g_set_laws(a in g_set(), b in g_set(), c in g_set()) {
+            laws::check(a, b, c);
+        }
+
+        #[test]
+        fn two_p_set_laws(a in two_p_set(), b in two_p_set(), c in two_p_set()) {
+            laws::check(a, b, c);
+        }
+    }
+
+    #[test]
+    fn removed_elements_stay_removed() {
+        let (mut a, mut b) = (TwoPSet::<u64>::new(), TwoPSet::new());
+        a.insert(1);
+        b.remove(1);
+        a.merge(&b);
+        a.insert(1);
+
+        assert!(!a.contains(&1));
+    }
+}
@@ -0,0 +1,29 @@
+use crdt::{GSet, GossipMessage};
+use maelstrom::{Error, Message};
+
+pub enum Command {
+    Add(u64),
+    Read,
+    Gossip(GossipMessage<GSet<u64>>),
+}
+
+impl TryFrom<Message> for Command {
+    type Error = Error;
+
+    fn try_from(value: Message) -> Result<Self, Self::Error> {
+        match value.msg_type() {
+            "add" => add(value),
+            "read" => Ok(Command::Read),
+            _ => GossipMessage::try_from(value).map(Command::Gossip),
+        }
+    }
+}
+
+fn add(message: Message) -> Result<Command, Error> {
+    match message.body()["element"].as_u64() {
+        Some(element) => Ok(Command::Add(element)),
+        None => Err(Error::malformed_request(
+            "add message missing `element` key",
+        )),
+    }
+}
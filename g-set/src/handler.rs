@@ -0,0 +1,43 @@
+use crate::command::Command;
+use crdt::{GSet, Gossip};
+use maelstrom::{Context, Handler};
+use serde_json::{json, Value};
+
+/// A grow-only set, gossiped to every other node.
+pub struct GSetHandler {
+    set: Gossip<GSet<u64>>,
+}
+
+impl GSetHandler {
+    pub fn new() -> Self {
+        Self { set: Gossip::new() }
+    }
+
+    fn add(&mut self, element: u64, ctx: Context) {
+        self.set.update(|set| _ = set.insert(element), &ctx);
+        ctx.reply(json!({"type": "add_ok"}));
+    }
+
+    fn read(&mut self, ctx: Context) {
+        let value: Value = self.set.state().clone().into();
+        ctx.reply(json!({"type": "read_ok", "value": value}));
+    }
+}
+
+impl Handler for GSetHandler {
+    type Command = Command;
+
+    fn init(&mut self, ctx: Context) {
+        let peers = ctx.node_ids().iter().filter(|id| *id != ctx.node_id());
+        self.set.set_neighbors(peers.cloned());
+        self.set.init(&ctx);
+    }
+
+    fn handle(&mut self, command: Command, ctx: Context) {
+        match command {
+            Command::Add(element) => self.add(element, ctx),
+            Command::Read => self.read(ctx),
+            Command::Gossip(message) => self.set.handle(message, &ctx),
+        }
+    }
+}
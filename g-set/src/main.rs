@@ -0,0 +1,11 @@
+mod command;
+mod handler;
+
+use handler::GSetHandler;
+use maelstrom::Node;
+use tokio::task::JoinError;
+
+#[tokio::main]
+async fn main() -> Result<(), JoinError> {
+    Node::from_handler(GSetHandler::new()).start().await
+}
@@ -0,0 +1,71 @@
+use maelstrom::{Error, Message};
+use serde_json::Value;
+use std::collections::HashMap;
+
+pub enum Command {
+    Send(String, Value),
+    Poll(HashMap<String, u64>),
+    CommitOffsets(HashMap<String, u64>),
+    ListCommittedOffsets(Vec<String>),
+}
+
+impl TryFrom<Message> for Command {
+    type Error = Error;
+
+    fn try_from(value: Message) -> Result<Self, Self::Error> {
+        match value.msg_type() {
+            "send" => send(value),
+            "poll" => poll(value),
+            "commit_offsets" => commit_offsets(value),
+            "list_committed_offsets" => list_committed_offsets(value),
+            msg_type => Err(Error::not_supported(msg_type)),
+        }
+    }
+}
+
+fn send(message: Message) -> Result<Command, Error> {
+    let body = message.body();
+    match (body["key"].as_str(), &body["msg"]) {
+        (Some(key), msg) if !msg.is_null() => Ok(Command::Send(key.to_string(), msg.clone())),
+        _ => Err(Error::malformed_request(
+            "send message missing `key` or `msg` key",
+        )),
+    }
+}
+
+fn poll(message: Message) -> Result<Command, Error> {
+    offsets(message).map(Command::Poll)
+}
+
+fn commit_offsets(message: Message) -> Result<Command, Error> {
+    offsets(message).map(Command::CommitOffsets)
+}
+
+fn list_committed_offsets(message: Message) -> Result<Command, Error> {
+    match message.body()["keys"].as_array() {
+        Some(keys) => {
+            let keys = keys
+                .iter()
+                .filter_map(|v| v.as_str())
+                .map(|s| s.to_string())
+                .collect();
+            Ok(Command::ListCommittedOffsets(keys))
+        }
+        None => Err(Error::malformed_request(
+            "list_committed_offsets message missing `keys` key",
+        )),
+    }
+}
+
+fn offsets(message: Message) -> Result<HashMap<String, u64>, Error> {
+    match message.body()["offsets"].as_object() {
+        Some(offsets) => {
+            let offsets = offsets
+                .iter()
+                .filter_map(|(key, offset)| offset.as_u64().map(|o| (key.to_string(), o)))
+                .collect();
+            Ok(offsets)
+        }
+        None => Err(Error::malformed_request("message missing `offsets` key")),
+    }
+}
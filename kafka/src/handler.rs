@@ -0,0 +1,276 @@
+use crate::command::Command;
+use maelstrom::{fnv1a, Context, Error, Handler, Message};
+use serde_json::{json, Map, Value};
+use std::{collections::HashMap, future::Future, time::Duration};
+use tokio::spawn;
+
+/// How long to wait for a key owner to answer a forwarded request.
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A kafka-style log service where every key is owned by a single node. The
+/// owner assigns offsets and keeps committed offsets for its keys; any other
+/// node forwards requests for that key to the owner and relays its reply.
+pub struct KafkaHandler {
+    logs: HashMap<String, Vec<Value>>,
+    committed: HashMap<String, u64>,
+}
+
+impl KafkaHandler {
+    pub fn new() -> Self {
+        Self {
+            logs: Default::default(),
+            committed: Default::default(),
+        }
+    }
+
+    fn send(&mut self, key: String, msg: Value, ctx: Context) {
+        let owner = owner(&key, &ctx);
+        if owner != ctx.node_id() {
+            let body = json!({"type": "send", "key": key, "msg": msg});
+            ctx.forward(owner.to_string(), body, FORWARD_TIMEOUT);
+            return;
+        }
+
+        let log = self.logs.entry(key).or_default();
+        let offset = log.len();
+        log.push(msg);
+
+        let reply = json!({"type": "send_ok", "offset": offset});
+        ctx.reply(reply);
+    }
+
+    fn poll(&mut self, offsets: HashMap<String, u64>, ctx: Context) {
+        let mut shards = shard(offsets, &ctx);
+        let local = shards.remove(ctx.node_id()).unwrap_or_default();
+
+        let msgs = local
+            .into_iter()
+            .map(|(key, offset)| {
+                let msgs = self.read(&key, offset);
+                (key, msgs)
+            })
+            .collect();
+
+        let requests = shards
+            .into_iter()
+            .map(|(owner, offsets)| {
+                let body = json!({"type": "poll", "offsets": offsets});
+                ctx.rpc(owner, body, FORWARD_TIMEOUT)
+            })
+            .collect();
+
+        gather(
+            ctx,
+            requests,
+            msgs,
+            "msgs",
+            |msgs| json!({"type": "poll_ok", "msgs": msgs}),
+        );
+    }
+
+    fn commit_offsets(&mut self, offsets: HashMap<String, u64>, ctx: Context) {
+        let mut shards = shard(offsets, &ctx);
+        let local = shards.remove(ctx.node_id()).unwrap_or_default();
+
+        for (key, offset) in local {
+            let committed = self.committed.entry(key).or_default();
+            *committed = offset.max(*committed);
+        }
+
+        let requests = shards
+            .into_iter()
+            .map(|(owner, offsets)| {
+                let body = json!({"type": "commit_offsets", "offsets": offsets});
+                ctx.rpc(owner, body, FORWARD_TIMEOUT)
+            })
+            .collect();
+
+        gather(
+            ctx,
+            requests,
+            Map::new(),
+            "offsets",
+            |_| json!({"type": "commit_offsets_ok"}),
+        );
+    }
+
+    fn list_committed_offsets(&mut self, keys: Vec<String>, ctx: Context) {
+        let mut shards = shard(keys.into_iter().map(|key| (key, ())), &ctx);
+        let local = shards.remove(ctx.node_id()).unwrap_or_default();
+
+        let offsets = local
+            .into_keys()
+            .filter_map(|key| {
+                let offset = self.committed.get(&key)?;
+                Some((key, Value::from(*offset)))
+            })
+            .collect();
+
+        let requests = shards
+            .into_iter()
+            .map(|(owner, keys)| {
+                let keys = keys.into_keys().collect::<Vec<_>>();
+                let body = json!({"type": "list_committed_offsets", "keys": keys});
+                ctx.rpc(owner, body, FORWARD_TIMEOUT)
+            })
+            .collect();
+
+        gather(
+            ctx,
+            requests,
+            offsets,
+            "offsets",
+            |offsets| json!({"type": "list_committed_offsets_ok", "offsets": offsets}),
+        );
+    }
+
+    fn read(&self, key: &str, offset: u64) -> Value {
+        let log = self.logs.get(key).map(Vec::as_slice).unwrap_or_default();
+        log.iter()
+            .enumerate()
+            .skip(offset as usize)
+            .map(|(offset, msg)| json!([offset, msg]))
+            .collect()
+    }
+}
+
+impl Handler for KafkaHandler {
+    type Command = Command;
+
+    fn handle(&mut self, command: Command, ctx: Context) {
+        match command {
+            Command::Send(key, msg) => self.send(key, msg, ctx),
+            Command::Poll(offsets) => self.poll(offsets, ctx),
+            Command::CommitOffsets(offsets) => self.commit_offsets(offsets, ctx),
+            Command::ListCommittedOffsets(keys) => self.list_committed_offsets(keys, ctx),
+        }
+    }
+}
+
+/// Picks the node that owns `key` by hashing it over the cluster's node ids.
+/// Before the node is initialized every key is owned locally. The hash is
+/// fixed, so nodes agree on owners however they were built.
+fn owner<'a>(key: &str, ctx: &'a Context) -> &'a str {
+    let node_ids = ctx.node_ids();
+    if node_ids.is_empty() {
+        return ctx.node_id();
+    }
+
+    let index = fnv1a(key.as_bytes()) % node_ids.len() as u64;
+    &node_ids[index as usize]
+}
+
+/// Groups per-key entries by the node owning each key.
+fn shard<T>(
+    entries: impl IntoIterator<Item = (String, T)>,
+    ctx: &Context,
+) -> HashMap<String, HashMap<String, T>> {
+    let mut shards: HashMap<String, HashMap<String, T>> = HashMap::new();
+    for (key, value) in entries {
+        let owner = owner(&key, ctx).to_string();
+        shards.entry(owner).or_default().insert(key, value);
+    }
+    shards
+}
+
+/// Waits for the replies to requests forwarded to other owners, merges the
+/// object at `field` of each into `merged` and replies to the client with the
+/// body built by `reply`. If any owner fails to answer, the client gets the
+/// error instead.
+fn gather<F>(
+    ctx: Context,
+    requests: Vec<F>,
+    mut merged: Map<String, Value>,
+    field: &'static str,
+    reply: impl FnOnce(Map<String, Value>) -> Value + Send + 'static,
+) where
+    F: Future<Output = Result<Message, Error>> + Send + 'static,
+{
+    spawn(async move {
+        for request in requests {
+            match request.await {
+                Ok(message) => {
+                    if let Some(values) = message.body()[field].as_object() {
+                        merged.extend(values.clone());
+                    }
+                }
+                Err(error) => return ctx.reply(error),
+            }
+        }
+
+        ctx.reply(reply(merged));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maelstrom::testing::Network;
+
+    const NODES: [&str; 3] = ["n0", "n1", "n2"];
+
+    async fn cluster() -> Network {
+        let network = Network::new();
+        for node_id in NODES {
+            network.start(node_id, KafkaHandler::new());
+        }
+        network.init(&NODES).await;
+        network
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn forwards_requests_to_key_owners() {
+        let network = cluster().await;
+        let keys = (0..6).map(|key| format!("k{}", key)).collect::<Vec<_>>();
+
+        // every node sends to every key, so most sends are forwarded
+        for (i, key) in keys.iter().enumerate() {
+            for (j, node_id) in NODES.iter().enumerate() {
+                let body = json!({"type": "send", "key": key, "msg": i * 10 + j});
+                let reply = network.request(node_id, body).await;
+                assert_eq!(reply["type"], "send_ok");
+                assert_eq!(reply["offset"], j);
+            }
+        }
+
+        let offsets = keys
+            .iter()
+            .map(|key| (key.clone(), json!(1)))
+            .collect::<Map<_, _>>();
+        let reply = network
+            .request("n1", json!({"type": "poll", "offsets": offsets}))
+            .await;
+        for (i, key) in keys.iter().enumerate() {
+            let expected = json!([[1, i * 10 + 1], [2, i * 10 + 2]]);
+            assert_eq!(reply["msgs"][key], expected, "{}", key);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn gathers_committed_offsets_from_owners() {
+        let network = cluster().await;
+        let keys = (0..6).map(|key| format!("k{}", key)).collect::<Vec<_>>();
+
+        let offsets = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (key.clone(), json!(i)))
+            .collect::<Map<_, _>>();
+        let body = json!({"type": "commit_offsets", "offsets": offsets});
+        assert_eq!(
+            network.request("n0", body).await["type"],
+            "commit_offsets_ok"
+        );
+
+        // offsets only move forward
+        let body = json!({"type": "commit_offsets", "offsets": {"k5": 1}});
+        network.request("n2", body).await;
+
+        let mut queried = keys.clone();
+        queried.push("unknown".to_string());
+        let body = json!({"type": "list_committed_offsets", "keys": queried});
+        let reply = network.request("n2", body).await;
+        assert_eq!(reply["type"], "list_committed_offsets_ok");
+        assert_eq!(reply["offsets"], Value::Object(offsets));
+    }
+}
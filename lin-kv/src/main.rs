@@ -0,0 +1,28 @@
+mod op;
+mod store;
+
+use maelstrom::Node;
+use paxos::Paxos;
+use raft::Raft;
+use std::env;
+use store::Store;
+use tokio::task::JoinError;
+
+#[tokio::main]
+async fn main() -> Result<(), JoinError> {
+    let consensus = env::var("LIN_KV_CONSENSUS").unwrap_or_else(|_| "raft".to_string());
+
+    match consensus.as_str() {
+        "raft" => {
+            Node::from_handler(Raft::new(Store::default()))
+                .start()
+                .await
+        }
+        "paxos" => {
+            Node::from_handler(Paxos::new(Store::default()))
+                .start()
+                .await
+        }
+        other => panic!("unknown consensus protocol: {}", other),
+    }
+}
@@ -0,0 +1,100 @@
+use maelstrom::Error;
+use serde_json::{json, Value};
+
+/// A key/value operation requested by a client.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Op {
+    Read(Value),
+    Write(Value, Value),
+
+    /// Swaps the value at a key from one value to another. If the key is
+    /// missing, it is created with the new value when the last field, the
+    /// request's `create_if_not_exists`, is set.
+    Cas(Value, Value, Value, bool),
+}
+
+impl TryFrom<&Value> for Op {
+    type Error = Error;
+
+    fn try_from(body: &Value) -> Result<Self, Self::Error> {
+        let key = match &body["key"] {
+            Value::Null => return Err(Error::malformed_request("message missing `key` key")),
+            key => key.clone(),
+        };
+
+        match body["type"].as_str() {
+            Some("read") => Ok(Op::Read(key)),
+            Some("write") => Ok(Op::Write(key, body["value"].clone())),
+            Some("cas") => {
+                let create = body["create_if_not_exists"].as_bool().unwrap_or(false);
+                Ok(Op::Cas(
+                    key,
+                    body["from"].clone(),
+                    body["to"].clone(),
+                    create,
+                ))
+            }
+            Some(msg_type) => Err(Error::not_supported(msg_type)),
+            None => Err(Error::malformed_request("message missing `type` key")),
+        }
+    }
+}
+
+impl TryFrom<Value> for Op {
+    type Error = Error;
+
+    fn try_from(body: Value) -> Result<Self, Self::Error> {
+        Op::try_from(&body)
+    }
+}
+
+impl From<Op> for Value {
+    fn from(op: Op) -> Self {
+        Value::from(&op)
+    }
+}
+
+impl From<&Op> for Value {
+    fn from(op: &Op) -> Self {
+        match op {
+            Op::Read(key) => json!({"type": "read", "key": key}),
+            Op::Write(key, value) => json!({"type": "write", "key": key, "value": value}),
+            Op::Cas(key, from, to, create) => json!({
+                "type": "cas",
+                "key": key,
+                "from": from,
+                "to": to,
+                "create_if_not_exists": create,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_ops_as_request_bodies() {
+        let ops = [
+            Op::Read(json!(1)),
+            Op::Write(json!("a"), json!([1, 2])),
+            Op::Cas(json!(1), json!(2), json!(3), false),
+            Op::Cas(json!(1), Value::Null, json!(3), true),
+        ];
+        for op in ops {
+            assert_eq!(Op::try_from(Value::from(&op)).unwrap(), op);
+        }
+
+        let body = json!({"type": "cas", "key": 1, "from": 2, "to": 3});
+        assert_eq!(
+            Op::try_from(body).unwrap(),
+            Op::Cas(json!(1), json!(2), json!(3), false)
+        );
+
+        let missing_key = Op::try_from(json!({"type": "read"})).unwrap_err();
+        assert_eq!(missing_key.code(), 12);
+        let unknown = Op::try_from(json!({"type": "delete", "key": 1})).unwrap_err();
+        assert_eq!(unknown.code(), 10);
+    }
+}
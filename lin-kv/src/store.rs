@@ -0,0 +1,86 @@
+use crate::op::Op;
+use maelstrom::{Error, StateMachine};
+use serde_json::{json, Map, Value};
+
+/// The replicated key/value state machine. Keys are stored in their JSON
+/// encoding.
+#[derive(Default)]
+pub struct Store {
+    data: Map<String, Value>,
+}
+
+impl StateMachine for Store {
+    type Command = Op;
+
+    fn apply(&mut self, op: &Op) -> Value {
+        match op {
+            Op::Read(key) => match self.data.get(&key.to_string()) {
+                Some(value) => json!({"type": "read_ok", "value": value}),
+                None => Error::key_does_not_exist(&format!("key {} not found", key)).into(),
+            },
+            Op::Write(key, value) => {
+                self.data.insert(key.to_string(), value.clone());
+                json!({"type": "write_ok"})
+            }
+            Op::Cas(key, from, to, create) => match self.data.get_mut(&key.to_string()) {
+                Some(value) if value == from => {
+                    *value = to.clone();
+                    json!({"type": "cas_ok"})
+                }
+                Some(value) => {
+                    let text = format!("expected {} but was {}", from, value);
+                    Error::precondition_failed(&text).into()
+                }
+                None if *create => {
+                    self.data.insert(key.to_string(), to.clone());
+                    json!({"type": "cas_ok"})
+                }
+                None => Error::key_does_not_exist(&format!("key {} not found", key)).into(),
+            },
+        }
+    }
+
+    fn snapshot(&self) -> Value {
+        Value::Object(self.data.clone())
+    }
+
+    fn restore(&mut self, snapshot: Value) {
+        self.data = match snapshot {
+            Value::Object(data) => data,
+            _ => Map::new(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_writes_and_swaps_values() {
+        let mut store = Store::default();
+        let read = store.apply(&Op::Read(json!(1)));
+        assert_eq!(read["code"], Error::KEY_DOES_NOT_EXIST);
+        let cas = store.apply(&Op::Cas(json!(1), json!(2), json!(3), false));
+        assert_eq!(cas["code"], Error::KEY_DOES_NOT_EXIST);
+
+        let cas = store.apply(&Op::Cas(json!(1), json!(2), json!(3), true));
+        assert_eq!(cas["type"], "cas_ok");
+        let read = store.apply(&Op::Read(json!(1)));
+        assert_eq!(read, json!({"type": "read_ok", "value": 3}));
+
+        let cas = store.apply(&Op::Cas(json!(1), json!(2), json!(4), true));
+        assert_eq!(cas["code"], Error::PRECONDITION_FAILED);
+        let cas = store.apply(&Op::Cas(json!(1), json!(3), json!(4), false));
+        assert_eq!(cas["type"], "cas_ok");
+
+        // keys are compared by their JSON encoding
+        store.apply(&Op::Write(json!("1"), json!(5)));
+        assert_eq!(store.apply(&Op::Read(json!(1)))["value"], 4);
+        assert_eq!(store.apply(&Op::Read(json!("1")))["value"], 5);
+
+        let mut restored = Store::default();
+        restored.restore(store.snapshot());
+        assert_eq!(restored.apply(&Op::Read(json!(1)))["value"], 4);
+    }
+}
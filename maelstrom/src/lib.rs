@@ -1,8 +1,14 @@
 //! This libraries makes it easy to implement [Maelstrom] nodes in Rust.
 //!
 //! [Maelstrom]: https://github.com/jepsen-io/maelstrom/tree/main
+mod hash;
 mod protocol;
 mod rt;
+mod state_machine;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
+pub use hash::*;
 pub use protocol::*;
 pub use rt::*;
+pub use state_machine::*;
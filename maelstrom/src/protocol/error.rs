@@ -1,4 +1,5 @@
 use serde_json::{json, Value};
+use std::fmt;
 
 /// An error type for Maelstrom message bodies.
 ///
@@ -15,23 +16,92 @@ pub struct Error {
 }
 
 impl Error {
+    /// The `timeout` error code.
+    pub const TIMEOUT: u32 = 0;
+
+    /// The `key-does-not-exist` error code.
+    pub const KEY_DOES_NOT_EXIST: u32 = 20;
+
+    /// The `precondition-failed` error code.
+    pub const PRECONDITION_FAILED: u32 = 22;
+
+    /// The `txn-conflict` error code.
+    pub const TXN_CONFLICT: u32 = 30;
+
     /// Creates a new error with a `code` and optional `text`.
     fn new(code: u32, text: Option<String>) -> Self {
         Self { code, text }
     }
 
+    /// Parses an error from a message body, if the body is an `error` message.
+    pub fn from_body(body: &Value) -> Option<Self> {
+        if body["type"].as_str() != Some("error") {
+            return None;
+        }
+
+        let code = body["code"].as_u64().unwrap_or(13) as u32;
+        let text = body["text"].as_str().map(|s| s.to_string());
+        Some(Self::new(code, text))
+    }
+
+    /// Creates a `timeout` error with the given text.
+    pub fn timeout(text: &str) -> Self {
+        Self::new(Self::TIMEOUT, Some(text.to_string()))
+    }
+
     /// Creates a `not-supported` error for the given message type.
     pub fn not_supported(msg_type: &str) -> Self {
         let text = format!("message type not supported: {}", msg_type);
         Self::new(10, Some(text))
     }
 
+    /// Creates a `temporarily-unavailable` error with the given text.
+    pub fn temporarily_unavailable(text: &str) -> Self {
+        Self::new(11, Some(text.to_string()))
+    }
+
     /// Creates a `malformed-request` error with the given text
     pub fn malformed_request(text: &str) -> Self {
-        Self::new(13, Some(text.to_string()))
+        Self::new(12, Some(text.to_string()))
+    }
+
+    /// Creates a `key-does-not-exist` error with the given text.
+    pub fn key_does_not_exist(text: &str) -> Self {
+        Self::new(Self::KEY_DOES_NOT_EXIST, Some(text.to_string()))
+    }
+
+    /// Creates a `precondition-failed` error with the given text.
+    pub fn precondition_failed(text: &str) -> Self {
+        Self::new(Self::PRECONDITION_FAILED, Some(text.to_string()))
+    }
+
+    /// Creates a `txn-conflict` error with the given text.
+    pub fn txn_conflict(text: &str) -> Self {
+        Self::new(Self::TXN_CONFLICT, Some(text.to_string()))
+    }
+
+    /// The numeric error code.
+    pub fn code(&self) -> u32 {
+        self.code
+    }
+
+    /// The error text, if any.
+    pub fn text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.text {
+            Some(text) => write!(f, "error {}: {}", self.code, text),
+            None => write!(f, "error {}", self.code),
+        }
     }
 }
 
+impl std::error::Error for Error {}
+
 impl From<Error> for Value {
     /// Converts an [Error] into a JSON [Value] matching the Maelstrom error
     /// message specification.
@@ -1,10 +1,27 @@
-use super::SendMessage;
-use serde_json::Value;
-use tokio::sync::mpsc::UnboundedSender;
+use super::{
+    node::{causal::Causal, reliable::Stream, routing::Routes, total_order},
+    Cluster, SendMessage, Topology,
+};
+use crate::{Error, Message};
+use serde_json::{json, Value};
+use std::{future::Future, sync::Arc, time::Duration};
+use tokio::{
+    spawn,
+    sync::{
+        mpsc::{UnboundedSender, WeakUnboundedSender},
+        oneshot,
+    },
+    time::{interval, sleep, timeout},
+};
 
 /// A [Handler](super::handler::Handler) context. The handler context keeps
 /// track of the current message and enables handlers to send replies or
 /// other arbitrary messages.
+///
+/// Contexts are cheap to clone and may outlive the call to
+/// [Handler::handle](super::handler::Handler::handle) they were created for,
+/// so a handler can hold on to one and reply to the current message later,
+/// e.g. from a spawned task.
 #[derive(Clone)]
 pub struct Context {
     /// the source node id for the current message
@@ -13,23 +30,60 @@ pub struct Context {
     /// the current message id
     msg_id: Option<u64>,
 
+    /// the local node and its peers
+    cluster: Arc<Cluster>,
+
     /// the channel to send messages/replies
     send_tx: UnboundedSender<SendMessage>,
+
+    /// the channel feeding the handler; weak, so that pending notifications
+    /// don't keep the node alive after its input is closed
+    message_tx: WeakUnboundedSender<Message>,
+
+    /// the vector clock for causal broadcasts
+    causal: Causal,
+
+    /// the next hop towards each node, for routed messages
+    routes: Routes,
 }
 
 impl Context {
     pub(super) fn new(
         src: String,
         msg_id: Option<u64>,
+        cluster: Arc<Cluster>,
         send_tx: UnboundedSender<SendMessage>,
+        message_tx: WeakUnboundedSender<Message>,
+        causal: Causal,
+        routes: Routes,
     ) -> Self {
         Self {
             src,
             msg_id,
+            cluster,
             send_tx,
+            message_tx,
+            causal,
+            routes,
         }
     }
 
+    /// The source of the current message.
+    pub fn src(&self) -> &str {
+        &self.src
+    }
+
+    /// The id of the local node. Empty until the node is initialized.
+    pub fn node_id(&self) -> &str {
+        &self.cluster.node_id
+    }
+
+    /// The ids of all nodes in the cluster, including the local node. Empty
+    /// until the node is initialized.
+    pub fn node_ids(&self) -> &[String] {
+        &self.cluster.node_ids
+    }
+
     /// Send a reply to the current message. This is equivalent to [send] with
     /// `dest` set to the source of the current message and `in_reply_to` set to
     /// the current message id.
@@ -39,9 +93,189 @@ impl Context {
 
     /// Send a message to another node.
     pub fn send(&self, dest: String, in_reply_to: Option<u64>, body: impl Into<Value>) {
-        let message = SendMessage::send(dest, in_reply_to, body);
+        self.submit(SendMessage::send(dest, in_reply_to, body));
+    }
+
+    /// Sets the graph [send_routed](Context::send_routed) routes messages
+    /// over, typically the one received in a `topology` message. Nodes that
+    /// forward messages for this one must set the same graph. The node must
+    /// be initialized.
+    pub fn set_topology(&self, topology: &Topology) {
+        self.routes.set(topology, self.node_id());
+    }
+
+    /// Send a message to another node along a shortest path in the graph
+    /// set by [set_topology](Context::set_topology), so only neighbors talk
+    /// to each other directly. Nodes along the way forward the message
+    /// without handing it to their handlers, and the destination's handler
+    /// sees it as coming from this node. Messages to nodes with no known
+    /// route are sent directly. Like [send](Context::send), delivery isn't
+    /// guaranteed, and the message can't be replied to.
+    pub fn send_routed(&self, dest: String, body: impl Into<Value>) {
+        let (hop, body) = self.routes.route(self.node_id(), dest, body.into());
+        self.send(hop, None, body);
+    }
+
+    /// Send a message to another node reliably: the runtime numbers it,
+    /// retransmits it until the destination acknowledges it, and delivers it
+    /// to the destination's handler exactly once, so it gets through
+    /// partitions once they heal. Messages may still be handled in a
+    /// different order than they were sent in; see
+    /// [send_fifo](Context::send_fifo). The destination must run this
+    /// runtime too.
+    pub fn send_reliable(&self, dest: String, body: impl Into<Value>) {
+        self.submit(SendMessage::reliable(dest, Stream::Unordered, body));
+    }
+
+    /// Like [send_reliable](Context::send_reliable), but the destination's
+    /// handler receives the messages sent this way from this node in the
+    /// order they were sent, holding back any that arrive early until the
+    /// ones before them arrive.
+    pub fn send_fifo(&self, dest: String, body: impl Into<Value>) {
+        self.submit(SendMessage::reliable(dest, Stream::Fifo, body));
+    }
+
+    /// Send a message to every other node in the cluster, reliably and in
+    /// causal order: each node's handler receives it only after every
+    /// causal broadcast this node had sent or received before sending it.
+    /// The message is stamped with a vector clock and sent as with
+    /// [send_reliable](Context::send_reliable); receivers hold it back until
+    /// its dependencies have been delivered. The local handler doesn't
+    /// receive its own broadcasts. The node must be initialized.
+    pub fn broadcast_causal(&self, body: impl Into<Value>) {
+        let body = self.causal.stamp(self.node_id(), body.into());
+        for dest in self.node_ids().iter().filter(|id| *id != self.node_id()) {
+            let reliable = SendMessage::reliable(dest.to_string(), Stream::Unordered, body.clone());
+            self.submit(reliable);
+        }
+    }
+
+    /// Send a message to every node in the cluster, including this one, in
+    /// total order: all handlers receive the messages broadcast this way, by
+    /// any node, in the same order.
+    ///
+    /// Broadcasts are ordered by a sequencer, the node with the smallest id,
+    /// which relays them to the others reliably; see
+    /// [send_reliable](Context::send_reliable). They make no progress while
+    /// the sequencer is unreachable. Handlers see each broadcast as coming
+    /// from the node that sent it, and should not reply to it. The node must
+    /// be initialized.
+    pub fn broadcast_total(&self, body: impl Into<Value>) {
+        let Some(sequencer) = total_order::sequencer(&self.cluster) else {
+            return;
+        };
+
+        let mut body = body.into();
+        body[total_order::STAGE] = Value::from(total_order::SUBMIT);
+        match sequencer == self.node_id() {
+            true => _ = self.deliver(body),
+            false => {
+                let sequencer = sequencer.to_string();
+                self.submit(SendMessage::reliable(sequencer, Stream::Unordered, body));
+            }
+        }
+    }
+
+    /// Send a request to another node and wait for its reply.
+    ///
+    /// The request is sent immediately; the returned future resolves to the
+    /// reply message, or to an error if the reply is an `error` message or if
+    /// no reply arrives within `timeout`. Replies to requests sent this way
+    /// are never passed to the handler.
+    pub fn rpc(
+        &self,
+        dest: String,
+        body: impl Into<Value>,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<Message, Error>> + Send + 'static {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.submit(SendMessage::rpc(dest, body, reply_tx));
+        wait_reply(reply_rx, timeout)
+    }
+
+    /// Forward the current message body to another node and relay its reply
+    /// (or an error, on timeout) back to the source of the current message.
+    pub fn forward(&self, dest: String, body: impl Into<Value>, timeout: Duration) {
+        let reply = self.rpc(dest, body, timeout);
+        let ctx = self.clone();
+
+        spawn(async move {
+            match reply.await {
+                Ok(message) => ctx.reply(message.body().clone()),
+                Err(error) => ctx.reply(error),
+            }
+        });
+    }
+
+    /// Deliver a message with the given body to the local handler, as if this
+    /// node had sent it to itself. This feeds timers and the results of
+    /// spawned tasks back into the handler's command stream.
+    pub fn notify(&self, body: impl Into<Value>) {
+        self.deliver(body.into());
+    }
+
+    /// Like [notify](Context::notify), but after `delay` has elapsed.
+    pub fn notify_after(&self, delay: Duration, body: impl Into<Value>) {
+        let (ctx, body) = (self.clone(), body.into());
+        spawn(async move {
+            sleep(delay).await;
+            ctx.deliver(body);
+        });
+    }
+
+    /// Like [notify](Context::notify), but repeatedly, once every `period`,
+    /// until the node stops.
+    pub fn notify_every(&self, period: Duration, body: impl Into<Value>) {
+        let (ctx, body) = (self.clone(), body.into());
+        spawn(async move {
+            let mut interval = interval(period);
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+                if !ctx.deliver(body.clone()) {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Delivers a notification, returning whether the node is still running.
+    pub(super) fn deliver(&self, body: Value) -> bool {
+        let node_id = self.node_id();
+        let json = json!({"src": node_id, "dest": node_id, "body": body});
+
+        let message = match Message::from_json(json) {
+            Ok(message) => message,
+            Err(err) => {
+                eprintln!("dropping invalid notification: {}", err);
+                return true;
+            }
+        };
+
+        match self.message_tx.upgrade() {
+            Some(message_tx) => message_tx.send(message).is_ok(),
+            None => false,
+        }
+    }
+
+    fn submit(&self, message: SendMessage) {
         if let Err(err) = self.send_tx.send(message) {
             eprintln!("send channel closed; dropping message {:?}", err.0);
         }
     }
 }
+
+async fn wait_reply(
+    reply_rx: oneshot::Receiver<Message>,
+    duration: Duration,
+) -> Result<Message, Error> {
+    match timeout(duration, reply_rx).await {
+        Ok(Ok(message)) => match Error::from_body(message.body()) {
+            Some(error) => Err(error),
+            None => Ok(message),
+        },
+        Ok(Err(_)) => Err(Error::temporarily_unavailable("node is shutting down")),
+        Err(_) => Err(Error::timeout("timed out waiting for reply")),
+    }
+}
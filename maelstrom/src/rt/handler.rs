@@ -16,6 +16,13 @@ pub trait Handler {
     /// provided [Context].
     fn handle(&mut self, command: Self::Command, ctx: Context);
 
+    /// Initializes the handler. The runtime calls this once, after the node
+    /// receives its `init` message and before handling any other command. The
+    /// runtime answers `init` itself, so handlers should not reply here; the
+    /// context is meant for learning about the cluster, sending messages and
+    /// scheduling notifications.
+    fn init(&mut self, _ctx: Context) {}
+
     /// Stops the handler. The runtime calls this methods and waits for it to
     /// finish before exiting. If your handler spawns async tasks, you can
     /// `await` them here to make sure they complete before terminating.
@@ -0,0 +1,92 @@
+use crate::{Context, Error};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// A client for one of Maelstrom's built-in [key/value services][services].
+///
+/// Each operation is an RPC to the service issued through a [Context], so
+/// the returned futures are typically awaited from a spawned task. Service
+/// errors such as `key-does-not-exist` or `precondition-failed` are
+/// returned as [Error]s, and so are timeouts.
+///
+/// [services]: https://github.com/jepsen-io/maelstrom/blob/main/doc/services.md
+#[derive(Clone, Copy, Debug)]
+pub struct Kv {
+    service: &'static str,
+    timeout: Duration,
+}
+
+impl Kv {
+    const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// A client for the linearizable `lin-kv` service.
+    pub const fn lin() -> Self {
+        Self::new("lin-kv")
+    }
+
+    /// A client for the sequentially consistent `seq-kv` service.
+    pub const fn seq() -> Self {
+        Self::new("seq-kv")
+    }
+
+    /// A client for the last-write-wins `lww-kv` service.
+    pub const fn lww() -> Self {
+        Self::new("lww-kv")
+    }
+
+    const fn new(service: &'static str) -> Self {
+        Self {
+            service,
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Returns a client that waits up to `timeout` for each reply.
+    pub const fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// Reads the value of `key`.
+    pub async fn read(&self, ctx: &Context, key: impl Into<Value>) -> Result<Value, Error> {
+        let body = json!({"type": "read", "key": key.into()});
+        let reply = ctx
+            .rpc(self.service.to_string(), body, self.timeout)
+            .await?;
+        Ok(reply.body()["value"].clone())
+    }
+
+    /// Writes `value` to `key`.
+    pub async fn write(
+        &self,
+        ctx: &Context,
+        key: impl Into<Value>,
+        value: impl Into<Value>,
+    ) -> Result<(), Error> {
+        let body = json!({"type": "write", "key": key.into(), "value": value.into()});
+        ctx.rpc(self.service.to_string(), body, self.timeout)
+            .await?;
+        Ok(())
+    }
+
+    /// Sets `key` to `to` if its current value is `from`. If `create` is set
+    /// and the key doesn't exist, it is created with value `to`.
+    pub async fn cas(
+        &self,
+        ctx: &Context,
+        key: impl Into<Value>,
+        from: impl Into<Value>,
+        to: impl Into<Value>,
+        create: bool,
+    ) -> Result<(), Error> {
+        let body = json!({
+            "type": "cas",
+            "key": key.into(),
+            "from": from.into(),
+            "to": to.into(),
+            "create_if_not_exists": create,
+        });
+        ctx.rpc(self.service.to_string(), body, self.timeout)
+            .await?;
+        Ok(())
+    }
+}
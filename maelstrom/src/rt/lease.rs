@@ -0,0 +1,404 @@
+use super::Kv;
+use crate::{Context, Error};
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::{spawn, sync::oneshot, time::timeout};
+
+/// Lease-based leader election on top of the `lin-kv` service.
+///
+/// All nodes contending for a lease use the same key, which holds
+/// `{"leader": node_id, "expiry": millis}`. While [acquiring](Lease::acquire),
+/// a background task `cas`es the key to claim it once the current lease has
+/// expired, and the holder renews it every quarter of the lease duration.
+/// Expiry times are wall-clock milliseconds since the Unix epoch, so nodes
+/// are assumed to share a clock, as they do under Maelstrom.
+///
+/// Whenever the task's view of the leader changes, the handler receives a
+/// `lease_changed` message, which parses as a [Leadership]. The holder
+/// considers its lease lost a quarter of the duration before it expires, so
+/// it hears about it before any other node can take over. When a `cas`
+/// fails with `precondition-failed` or `key-does-not-exist`, the task
+/// re-reads the key; when it times out, it retries without assuming the
+/// outcome either way.
+#[derive(Debug)]
+pub struct Lease {
+    key: String,
+    duration: Duration,
+
+    /// stops the background task when sent to or dropped
+    stop_tx: Option<oneshot::Sender<()>>,
+}
+
+impl Lease {
+    const DEFAULT_DURATION: Duration = Duration::from_secs(1);
+
+    /// A lease on the given `lin-kv` key.
+    pub fn new(key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            duration: Self::DEFAULT_DURATION,
+            stop_tx: None,
+        }
+    }
+
+    /// Returns a lease that lasts `duration` each time it is acquired or
+    /// renewed.
+    pub fn with_duration(self, duration: Duration) -> Self {
+        Self { duration, ..self }
+    }
+
+    /// Starts contending for the lease and tracking its holder, unless
+    /// already doing so. The node must be initialized.
+    pub fn acquire(&mut self, ctx: &Context) {
+        if self.stop_tx.is_some() {
+            return;
+        }
+
+        let (stop_tx, stop_rx) = oneshot::channel();
+        self.stop_tx = Some(stop_tx);
+
+        let campaign = Campaign {
+            key: self.key.clone(),
+            kv: Kv::lin().with_timeout(self.duration / 10),
+            duration: self.duration,
+            ctx: ctx.clone(),
+            lease: Value::Null,
+            stale: true,
+            leader: None,
+        };
+        spawn(campaign.run(stop_rx));
+    }
+
+    /// Stops contending for the lease, releasing it if held so that another
+    /// node can take over right away. The handler then receives a final
+    /// `lease_changed` message with no leader.
+    pub fn step_down(&mut self) {
+        if let Some(stop_tx) = self.stop_tx.take() {
+            let _ = stop_tx.send(());
+        }
+    }
+}
+
+/// A change of a [Lease]'s holder, as seen by the local node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Leadership {
+    pub key: String,
+
+    /// the node holding the lease, if any
+    pub leader: Option<String>,
+}
+
+impl Leadership {
+    /// Whether the lease is held by `node_id`.
+    pub fn held_by(&self, node_id: &str) -> bool {
+        self.leader.as_deref() == Some(node_id)
+    }
+}
+
+impl TryFrom<&Value> for Leadership {
+    type Error = Error;
+
+    fn try_from(body: &Value) -> Result<Self, Self::Error> {
+        match (body["key"].as_str(), &body["leader"]) {
+            (Some(key), Value::Null) => Ok(Self {
+                key: key.to_string(),
+                leader: None,
+            }),
+            (Some(key), Value::String(leader)) => Ok(Self {
+                key: key.to_string(),
+                leader: Some(leader.to_string()),
+            }),
+            _ => Err(Error::malformed_request(
+                "lease_changed message missing `key` or `leader` key",
+            )),
+        }
+    }
+}
+
+impl From<&Leadership> for Value {
+    fn from(leadership: &Leadership) -> Self {
+        json!({
+            "type": "lease_changed",
+            "key": leadership.key,
+            "leader": leadership.leader,
+        })
+    }
+}
+
+/// The background task behind a [Lease].
+struct Campaign {
+    key: String,
+    kv: Kv,
+    duration: Duration,
+    ctx: Context,
+
+    /// the last known value of the lease key, or null if it doesn't exist
+    lease: Value,
+
+    /// whether `lease` must be re-read before acting on it
+    stale: bool,
+
+    /// the leader last announced to the handler
+    leader: Option<String>,
+}
+
+impl Campaign {
+    async fn run(mut self, mut stop_rx: oneshot::Receiver<()>) {
+        loop {
+            let wait = self.step().await;
+
+            if !self.announce() {
+                // the node stopped
+                return;
+            }
+
+            if timeout(wait, &mut stop_rx).await.is_ok() {
+                break;
+            }
+        }
+
+        self.release().await;
+    }
+
+    /// Reads, claims or renews the lease as needed, returning how long to
+    /// wait before the next step.
+    async fn step(&mut self) -> Duration {
+        if self.stale {
+            match self.kv.read(&self.ctx, self.key.as_str()).await {
+                Ok(lease) => self.lease = lease,
+                Err(error) if error.code() == Error::KEY_DOES_NOT_EXIST => self.lease = Value::Null,
+                Err(_) => return self.duration / 10,
+            }
+            self.stale = false;
+        }
+
+        let now = now();
+        let renew_interval = self.duration / 4;
+
+        if let Some((holder, expiry)) = holder(&self.lease) {
+            if holder != self.ctx.node_id() && expiry > now {
+                // look again once it is due for renewal or expiry
+                self.stale = true;
+                return renew_interval.min(Duration::from_millis(expiry - now));
+            }
+        }
+
+        let expiry = now + self.duration.as_millis() as u64;
+        let lease = json!({"leader": self.ctx.node_id(), "expiry": expiry});
+        let create = self.lease.is_null();
+
+        let cas = self.kv.cas(
+            &self.ctx,
+            self.key.as_str(),
+            self.lease.clone(),
+            lease.clone(),
+            create,
+        );
+        match cas.await {
+            Ok(()) => {
+                self.lease = lease;
+                renew_interval
+            }
+            // either another node changed the key since we read it, or the
+            // cas timed out and may or may not have happened; in both cases
+            // the key must be read again
+            Err(_) => {
+                self.stale = true;
+                Duration::ZERO
+            }
+        }
+    }
+
+    /// The current leader according to the last known lease.
+    fn leader(&self) -> Option<String> {
+        let (holder, expiry) = holder(&self.lease)?;
+        let margin = match holder == self.ctx.node_id() {
+            true => self.duration.as_millis() as u64 / 4,
+            false => 0,
+        };
+
+        (expiry > now() + margin).then(|| holder.to_string())
+    }
+
+    /// Tells the handler about a change of leader, returning whether the
+    /// node is still running.
+    fn announce(&mut self) -> bool {
+        let leader = self.leader();
+        if leader == self.leader {
+            return true;
+        }
+
+        self.leader = leader;
+        self.notify()
+    }
+
+    async fn release(mut self) {
+        if self.leader.take().is_some() {
+            self.notify();
+        }
+
+        if let Some((holder, _)) = holder(&self.lease) {
+            if holder == self.ctx.node_id() {
+                let released = json!({"leader": null, "expiry": 0});
+                let cas = self.kv.cas(
+                    &self.ctx,
+                    self.key.as_str(),
+                    self.lease.clone(),
+                    released,
+                    false,
+                );
+                let _ = cas.await;
+            }
+        }
+    }
+
+    fn notify(&self) -> bool {
+        let leadership = Leadership {
+            key: self.key.clone(),
+            leader: self.leader.clone(),
+        };
+        self.ctx.deliver(Value::from(&leadership))
+    }
+}
+
+/// The holder and expiry of a lease value.
+fn holder(lease: &Value) -> Option<(&str, u64)> {
+    Some((lease["leader"].as_str()?, lease["expiry"].as_u64()?))
+}
+
+fn now() -> u64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH);
+    now.map_or(0, |now| now.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::Network, Handler, Message};
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+    use tokio::time::sleep;
+
+    enum Command {
+        LeaseChanged(Leadership),
+        StepDown,
+    }
+
+    impl TryFrom<Message> for Command {
+        type Error = Error;
+
+        fn try_from(message: Message) -> Result<Self, Self::Error> {
+            match message.msg_type() {
+                "lease_changed" => Leadership::try_from(message.body()).map(Command::LeaseChanged),
+                "step_down" => Ok(Command::StepDown),
+                msg_type => Err(Error::not_supported(msg_type)),
+            }
+        }
+    }
+
+    /// The leader each node believes in, by node id.
+    type Leaders = Arc<Mutex<HashMap<String, Option<String>>>>;
+
+    struct Elector {
+        lease: Lease,
+        leaders: Leaders,
+    }
+
+    impl Handler for Elector {
+        type Command = Command;
+
+        fn init(&mut self, ctx: Context) {
+            self.lease.acquire(&ctx);
+        }
+
+        fn handle(&mut self, command: Self::Command, ctx: Context) {
+            match command {
+                Command::LeaseChanged(leadership) => {
+                    let mut leaders = self.leaders.lock().unwrap();
+                    leaders.insert(ctx.node_id().to_string(), leadership.leader);
+                }
+                Command::StepDown => {
+                    self.lease.step_down();
+                    ctx.reply(json!({"type": "step_down_ok"}));
+                }
+            }
+        }
+    }
+
+    const NODES: [&str; 3] = ["n0", "n1", "n2"];
+
+    async fn cluster() -> (Network, Leaders) {
+        let network = Network::new();
+        let leaders = Leaders::default();
+        for node_id in NODES {
+            let lease = Lease::new("leader").with_duration(Duration::from_millis(200));
+            let leaders = leaders.clone();
+            network.start(node_id, Elector { lease, leaders });
+        }
+
+        network.init(&NODES).await;
+        (network, leaders)
+    }
+
+    /// The leader all of `node_ids` agree on, if any.
+    fn agreement(leaders: &Leaders, node_ids: &[&str]) -> Option<String> {
+        let leaders = leaders.lock().unwrap();
+        let mut views = node_ids
+            .iter()
+            .map(|id| leaders.get(*id).cloned().flatten());
+        let leader = views.next()??;
+        views
+            .all(|view| view.as_ref() == Some(&leader))
+            .then_some(leader)
+    }
+
+    /// Waits until all of `node_ids` agree on a leader other than `previous`,
+    /// and returns it.
+    async fn agreed_leader(leaders: &Leaders, node_ids: &[&str], previous: Option<&str>) -> String {
+        for _ in 0..100 {
+            match agreement(leaders, node_ids) {
+                Some(leader) if Some(leader.as_str()) != previous => return leader,
+                _ => sleep(Duration::from_millis(20)).await,
+            }
+        }
+
+        panic!("no agreed leader: {:?}", leaders.lock().unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn elects_a_single_leader() {
+        let (_network, leaders) = cluster().await;
+        let leader = agreed_leader(&leaders, &NODES, None).await;
+
+        // the lease is renewed, not passed around
+        sleep(Duration::from_millis(500)).await;
+        assert_eq!(agreement(&leaders, &NODES), Some(leader));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stepping_down_hands_over_the_lease() {
+        let (network, leaders) = cluster().await;
+        let leader = agreed_leader(&leaders, &NODES, None).await;
+
+        network.request(&leader, json!({"type": "step_down"})).await;
+
+        let others = NODES.iter().copied().filter(|id| *id != leader);
+        let others = others.collect::<Vec<_>>();
+        agreed_leader(&leaders, &others, Some(&leader)).await;
+        assert_eq!(leaders.lock().unwrap()[&leader], None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn lease_expires_when_the_leader_stops() {
+        let (network, leaders) = cluster().await;
+        let leader = agreed_leader(&leaders, &NODES, None).await;
+
+        network.stop(&leader);
+
+        let others = NODES.iter().copied().filter(|id| *id != leader);
+        let others = others.collect::<Vec<_>>();
+        agreed_leader(&leaders, &others, Some(&leader)).await;
+    }
+}
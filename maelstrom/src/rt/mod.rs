@@ -1,12 +1,21 @@
 mod context;
 mod handler;
+mod kv;
+mod lease;
 mod node;
+mod topology;
 
 pub use context::*;
 pub use handler::*;
+pub use kv::*;
+pub use lease::*;
 pub use node::*;
+pub use topology::*;
 
+use crate::Message;
+use node::reliable::Stream;
 use serde_json::Value;
+use tokio::sync::oneshot;
 
 #[derive(Debug)]
 enum SendMessage {
@@ -15,11 +24,30 @@ enum SendMessage {
         in_reply_to: Option<u64>,
         body: Value,
     },
+    Rpc {
+        dest: String,
+        body: Value,
+        reply_tx: oneshot::Sender<Message>,
+    },
     SetNodeId {
         dest: String,
         in_reply_to: Option<u64>,
         node_id: String,
     },
+    /// a message retransmitted until `dest` acknowledges it
+    Reliable {
+        dest: String,
+        stream: Stream,
+        body: Value,
+    },
+    /// `src` acknowledged the reliable message with sequence number `seq`
+    Acked {
+        src: String,
+        stream: Stream,
+        seq: u64,
+    },
+    /// retransmit the reliable messages that are overdue
+    Retransmit,
 }
 
 impl SendMessage {
@@ -31,6 +59,22 @@ impl SendMessage {
         }
     }
 
+    fn rpc(dest: String, body: impl Into<Value>, reply_tx: oneshot::Sender<Message>) -> Self {
+        Self::Rpc {
+            dest,
+            body: body.into(),
+            reply_tx,
+        }
+    }
+
+    fn reliable(dest: String, stream: Stream, body: impl Into<Value>) -> Self {
+        Self::Reliable {
+            dest,
+            stream,
+            body: body.into(),
+        }
+    }
+
     fn set_node_id(dest: String, in_reply_to: Option<u64>, node_id: String) -> Self {
         Self::SetNodeId {
             dest,
@@ -39,3 +83,11 @@ impl SendMessage {
         }
     }
 }
+
+/// The identity of the local node and its peers, as received in the `init`
+/// message.
+#[derive(Debug, Default)]
+struct Cluster {
+    node_id: String,
+    node_ids: Vec<String>,
+}
@@ -0,0 +1,24 @@
+use crate::Message;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::oneshot;
+
+/// Pending RPC replies, keyed by the `msg_id` of the request. The sender
+/// registers a callback when it assigns an id to an RPC request, and the
+/// handler loop takes it when the matching reply arrives.
+#[derive(Clone, Default)]
+pub struct Callbacks(Arc<Mutex<HashMap<u64, oneshot::Sender<Message>>>>);
+
+impl Callbacks {
+    pub fn register(&self, msg_id: u64, reply_tx: oneshot::Sender<Message>) {
+        let mut callbacks = self.0.lock().unwrap();
+        callbacks.retain(|_, reply_tx| !reply_tx.is_closed());
+        callbacks.insert(msg_id, reply_tx);
+    }
+
+    pub fn take(&self, in_reply_to: u64) -> Option<oneshot::Sender<Message>> {
+        self.0.lock().unwrap().remove(&in_reply_to)
+    }
+}
@@ -0,0 +1,220 @@
+use crate::{rt::Cluster, Message};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+
+/// The body field carrying a causal broadcast's vector clock.
+pub const CLOCK: &str = "causal_clock";
+
+/// Vector clock bookkeeping for causal broadcasts, shared by the contexts
+/// that stamp outgoing broadcasts and the handler loop that holds back
+/// incoming ones.
+///
+/// Clocks are arrays with one entry per node, in the order of the
+/// `node_ids` received in `init`. The local node's entry counts the
+/// broadcasts it sent, and every other entry counts the broadcasts from
+/// that node delivered to the handler. A broadcast stamped with the
+/// sender's clock is delivered once it is the sender's next broadcast and
+/// everything the sender had delivered before sending it was delivered
+/// here too.
+#[derive(Clone, Default)]
+pub struct Causal(Arc<Mutex<Clocks>>);
+
+#[derive(Default)]
+struct Clocks {
+    node_ids: Vec<String>,
+    clock: Vec<u64>,
+
+    /// broadcasts waiting for the ones they depend on, with the index of
+    /// their sender and their clock
+    held: Vec<(usize, Vec<u64>, Message)>,
+}
+
+impl Causal {
+    /// Sizes the clock for the cluster, forgetting earlier broadcasts.
+    pub fn init(&self, cluster: &Cluster) {
+        let mut clocks = self.0.lock().unwrap();
+        clocks.node_ids = cluster.node_ids.clone();
+        clocks.clock = vec![0; cluster.node_ids.len()];
+        clocks.held.clear();
+    }
+
+    /// Stamps a new broadcast from the local node `node_id`, returning the
+    /// body to send to every other node.
+    pub fn stamp(&self, node_id: &str, mut body: Value) -> Value {
+        let mut clocks = self.0.lock().unwrap();
+        if let Some(index) = clocks.index(node_id) {
+            clocks.clock[index] += 1;
+        }
+
+        body[CLOCK] = Value::from(clocks.clock.clone());
+        body
+    }
+
+    /// Holds back a broadcast until the broadcasts it depends on have been
+    /// delivered, returning the broadcasts that are ready for the handler
+    /// in an order that respects their dependencies. Broadcasts with a
+    /// malformed clock or from unknown nodes are delivered right away.
+    pub fn receive(&self, message: Message) -> Vec<Message> {
+        let mut clocks = self.0.lock().unwrap();
+        let (Some(sender), Some(clock)) = (clocks.index(message.src()), clocks.parse(&message))
+        else {
+            return vec![message];
+        };
+
+        if clock[sender] <= clocks.clock[sender] {
+            return vec![];
+        }
+        clocks.held.push((sender, clock, message));
+
+        let mut ready = vec![];
+        while let Some(position) = clocks.deliverable() {
+            let (sender, _, message) = clocks.held.remove(position);
+            clocks.clock[sender] += 1;
+            ready.push(message);
+        }
+        ready
+    }
+}
+
+impl Clocks {
+    fn index(&self, node_id: &str) -> Option<usize> {
+        self.node_ids.iter().position(|id| id == node_id)
+    }
+
+    fn parse(&self, message: &Message) -> Option<Vec<u64>> {
+        let clock = message.body()[CLOCK]
+            .as_array()?
+            .iter()
+            .map(Value::as_u64)
+            .collect::<Option<Vec<_>>>()?;
+        (clock.len() == self.clock.len()).then_some(clock)
+    }
+
+    /// The position of a held broadcast that can be delivered now.
+    fn deliverable(&self) -> Option<usize> {
+        self.held.iter().position(|(sender, clock, _)| {
+            clock
+                .iter()
+                .enumerate()
+                .all(|(index, count)| match index == *sender {
+                    true => *count == self.clock[index] + 1,
+                    false => *count <= self.clock[index],
+                })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::Network, Context, Error, Handler};
+    use serde_json::json;
+    use std::time::Duration;
+    use tokio::time::sleep;
+
+    fn broadcast(src: &str, value: u64, clock: [u64; 3]) -> Message {
+        let body = json!({"type": "value", "value": value, CLOCK: clock});
+        Message::from_json(json!({"src": src, "dest": "n2", "body": body})).unwrap()
+    }
+
+    fn values(messages: Vec<Message>) -> Vec<u64> {
+        messages
+            .iter()
+            .map(|message| message.body()["value"].as_u64().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn holds_back_until_dependencies_are_delivered() {
+        let causal = Causal::default();
+        causal.init(&Cluster {
+            node_id: "n2".to_string(),
+            node_ids: vec!["n0".to_string(), "n1".to_string(), "n2".to_string()],
+        });
+
+        // n1 broadcast 2 after delivering n0's 1, which overtakes it
+        assert!(values(causal.receive(broadcast("n1", 2, [1, 1, 0]))).is_empty());
+        // n0's second broadcast only depends on its first
+        assert!(values(causal.receive(broadcast("n0", 3, [2, 0, 0]))).is_empty());
+        assert_eq!(
+            values(causal.receive(broadcast("n0", 1, [1, 0, 0]))),
+            [1, 2, 3]
+        );
+        assert!(values(causal.receive(broadcast("n0", 1, [1, 0, 0]))).is_empty());
+
+        let stamped = causal.stamp("n2", json!({"type": "value"}));
+        assert_eq!(stamped[CLOCK], json!([2, 1, 1]));
+    }
+
+    enum Command {
+        Broadcast(u64),
+        Value(u64),
+    }
+
+    impl TryFrom<Message> for Command {
+        type Error = Error;
+
+        fn try_from(message: Message) -> Result<Self, Self::Error> {
+            let value = message.body()["value"].as_u64().unwrap_or_default();
+            match message.msg_type() {
+                "broadcast" => Ok(Command::Broadcast(value)),
+                "value" => Ok(Command::Value(value)),
+                msg_type => Err(Error::not_supported(msg_type)),
+            }
+        }
+    }
+
+    /// Records the values each node handles; n1 answers 1 with 2.
+    struct Recorder(Arc<Mutex<Vec<(String, u64)>>>);
+
+    impl Handler for Recorder {
+        type Command = Command;
+
+        fn handle(&mut self, command: Self::Command, ctx: Context) {
+            match command {
+                Command::Broadcast(value) => {
+                    ctx.broadcast_causal(json!({"type": "value", "value": value}));
+                    ctx.reply(json!({"type": "broadcast_ok"}));
+                }
+                Command::Value(value) => {
+                    let handled = (ctx.node_id().to_string(), value);
+                    self.0.lock().unwrap().push(handled);
+                    if ctx.node_id() == "n1" && value == 1 {
+                        ctx.broadcast_causal(json!({"type": "value", "value": 2}));
+                    }
+                }
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn delivers_broadcasts_after_their_causes() {
+        let network = Network::new();
+        let handled = Arc::new(Mutex::new(vec![]));
+        let nodes = ["n0", "n1", "n2"];
+        for node_id in nodes {
+            network.start(node_id, Recorder(handled.clone()));
+        }
+        network.init(&nodes).await;
+
+        network.isolate("n2");
+        network
+            .request("n0", json!({"type": "broadcast", "value": 1}))
+            .await;
+        sleep(Duration::from_millis(50)).await;
+        network.heal();
+
+        let at_n2 = || {
+            let handled = handled.lock().unwrap();
+            let at_n2 = handled.iter().filter(|(node_id, _)| node_id == "n2");
+            at_n2.map(|(_, value)| *value).collect::<Vec<_>>()
+        };
+        for _ in 0..50 {
+            if at_n2().len() == 2 {
+                break;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(at_n2(), [1, 2]);
+    }
+}
@@ -1,66 +1,196 @@
-use crate::{rt::SendMessage, Context, Error, Handler, Message};
+use super::{callbacks::Callbacks, causal, reliable, routing, total_order};
+use crate::{
+    rt::{Cluster, SendMessage},
+    Context, Error, Handler, Message,
+};
+use serde_json::json;
+use std::sync::Arc;
 use tokio::{
     spawn,
-    sync::mpsc::{UnboundedReceiver, UnboundedSender},
+    sync::mpsc::{UnboundedReceiver, UnboundedSender, WeakUnboundedSender},
 };
 
 pub fn start<H, C>(
     handler: H,
     message_rx: UnboundedReceiver<Message>,
+    message_tx: WeakUnboundedSender<Message>,
     send_tx: UnboundedSender<SendMessage>,
+    callbacks: Callbacks,
 ) where
     H: Handler<Command = C> + Send + 'static,
     C: TryFrom<Message, Error = Error> + Send,
 {
-    spawn(async move { handle_messages(handler, message_rx, send_tx).await });
+    let channels = Channels {
+        message_tx,
+        send_tx,
+        causal: causal::Causal::default(),
+        routes: routing::Routes::default(),
+    };
+    spawn(async move { handle_messages(handler, message_rx, channels, callbacks).await });
+}
+
+/// The channels and shared state a [Context] needs, bundled so they can be
+/// cloned into each one.
+struct Channels {
+    message_tx: WeakUnboundedSender<Message>,
+    send_tx: UnboundedSender<SendMessage>,
+    causal: causal::Causal,
+    routes: routing::Routes,
+}
+
+impl Channels {
+    fn context(&self, src: String, msg_id: Option<u64>, cluster: &Arc<Cluster>) -> Context {
+        let (message_tx, send_tx) = (self.message_tx.clone(), self.send_tx.clone());
+        let (causal, routes) = (self.causal.clone(), self.routes.clone());
+        Context::new(
+            src,
+            msg_id,
+            cluster.clone(),
+            send_tx,
+            message_tx,
+            causal,
+            routes,
+        )
+    }
 }
 
 async fn handle_messages<H, C>(
     mut handler: H,
     mut message_rx: UnboundedReceiver<Message>,
-    send_tx: UnboundedSender<SendMessage>,
+    channels: Channels,
+    callbacks: Callbacks,
 ) where
     H: Handler<Command = C> + Send,
     C: TryFrom<Message, Error = Error> + Send,
 {
+    let mut cluster = Arc::new(Cluster::default());
+    let mut inbox = reliable::Inbox::default();
+
     while let Some(message) = message_rx.recv().await {
+        if let Some(reply_tx) = message.in_reply_to().and_then(|id| callbacks.take(id)) {
+            let _ = reply_tx.send(message);
+            continue;
+        }
+
         match message.msg_type() {
-            "init" => handle_init(message, &send_tx),
-            _ => handle(message, &mut handler, &send_tx),
+            "init" => {
+                let (src, msg_id) = (message.src().to_string(), message.msg_id());
+                if let Some(initialized) = handle_init(message, &channels.send_tx) {
+                    channels.causal.init(&initialized);
+                    cluster = Arc::new(initialized);
+                    handler.init(channels.context(src, msg_id, &cluster));
+                }
+            }
+            reliable::ACK => {
+                if let Some((stream, seq)) = reliable::Stream::of(message.body()) {
+                    let src = message.src().to_string();
+                    let acked = SendMessage::Acked { src, stream, seq };
+                    let _ = channels.send_tx.send(acked);
+                }
+            }
+            _ => {
+                let routed = channels
+                    .routes
+                    .receive(message, &cluster, &channels.send_tx);
+                let Some(message) = routed else {
+                    continue;
+                };
+
+                for message in accept(message, &mut inbox, &channels.send_tx) {
+                    for message in order(message, &cluster, &channels) {
+                        handle(message, &mut handler, &cluster, &channels)
+                    }
+                }
+            }
         }
     }
 
     handler.stop().await;
 }
 
-fn handle_init(message: Message, send_tx: &UnboundedSender<SendMessage>) {
+fn handle_init(message: Message, send_tx: &UnboundedSender<SendMessage>) -> Option<Cluster> {
     let (src, in_reply_to) = (message.src().to_string(), message.msg_id());
-    let reply = match message.body()["node_id"].as_str() {
-        Some(node_id) => SendMessage::set_node_id(src, in_reply_to, node_id.to_string()),
+    let (reply, cluster) = match message.body()["node_id"].as_str() {
+        Some(node_id) => {
+            let node_ids = message.body()["node_ids"]
+                .as_array()
+                .map(|ids| {
+                    ids.iter()
+                        .filter_map(|id| id.as_str())
+                        .map(|id| id.to_string())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            let cluster = Cluster {
+                node_id: node_id.to_string(),
+                node_ids,
+            };
+
+            let reply = SendMessage::set_node_id(src, in_reply_to, node_id.to_string());
+            (reply, Some(cluster))
+        }
         None => {
             eprintln!("received init message without node_id");
             let body = Error::malformed_request("init message with missing node_id");
-            SendMessage::send(src, in_reply_to, body)
+            (SendMessage::send(src, in_reply_to, body), None)
         }
     };
 
     let _ = send_tx.send(reply);
+    cluster
+}
+
+/// Acknowledges reliable messages, returning the messages to handle:
+/// reliable messages only the first time they arrive and, on FIFO streams,
+/// in the order they were sent; all other messages as they arrive.
+fn accept(
+    message: Message,
+    inbox: &mut reliable::Inbox,
+    send_tx: &UnboundedSender<SendMessage>,
+) -> Vec<Message> {
+    let Some((stream, seq)) = reliable::Stream::of(message.body()) else {
+        return vec![message];
+    };
+
+    let (src, mut ack) = (message.src().to_string(), json!({"type": reliable::ACK}));
+    ack[stream.field()] = seq.into();
+    let _ = send_tx.send(SendMessage::send(src, None, ack));
+    inbox.receive(stream, seq, message)
+}
+
+/// Holds back causal broadcasts and routes total-order broadcasts, returning
+/// the messages ready for the handler.
+fn order(message: Message, cluster: &Cluster, channels: &Channels) -> Vec<Message> {
+    let ready = match message.body().get(causal::CLOCK) {
+        Some(_) => channels.causal.receive(message),
+        None => vec![message],
+    };
+
+    ready
+        .into_iter()
+        .filter_map(|message| total_order::receive(message, cluster, &channels.send_tx))
+        .collect()
 }
 
-fn handle<H, C>(message: Message, handler: &mut H, send_tx: &UnboundedSender<SendMessage>)
+fn handle<H, C>(message: Message, handler: &mut H, cluster: &Arc<Cluster>, channels: &Channels)
 where
     H: Handler<Command = C>,
     C: TryFrom<Message, Error = Error>,
 {
     let (src, in_reply_to) = (message.src().to_string(), message.msg_id());
+    let is_reply = message.in_reply_to().is_some();
     match C::try_from(message) {
         Ok(command) => {
-            let context = Context::new(src, in_reply_to, send_tx.clone());
+            let context = channels.context(src, in_reply_to, cluster);
             handler.handle(command, context);
         }
+        // replies nobody is waiting for anymore (e.g. after an RPC timed out)
+        // are dropped; answering them with an error could bounce forever
+        Err(_) if is_reply => {}
         Err(error) => {
             let reply = SendMessage::send(src, in_reply_to, error);
-            let _ = send_tx.send(reply);
+            let _ = channels.send_tx.send(reply);
         }
     }
 }
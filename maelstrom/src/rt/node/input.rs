@@ -1,7 +1,7 @@
 use crate::{Message, MessageValidationError};
 use serde_json::Value;
 use std::io::{BufReader, Read};
-use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::mpsc::UnboundedSender;
 
 #[derive(Debug, thiserror::Error)]
 enum InputError {
@@ -12,10 +12,8 @@ enum InputError {
     Validation(MessageValidationError),
 }
 
-pub fn start(reader: impl Read + Send + 'static) -> UnboundedReceiver<Message> {
-    let (message_tx, message_rx) = unbounded_channel();
+pub fn start(reader: impl Read + Send + 'static, message_tx: UnboundedSender<Message>) {
     tokio::task::spawn_blocking(move || read_messages(reader, message_tx));
-    message_rx
 }
 
 fn read_messages(reader: impl Read, message_tx: UnboundedSender<Message>) {
@@ -1,11 +1,16 @@
+mod callbacks;
+pub(super) mod causal;
 mod handler;
 mod input;
 mod output;
+pub(super) mod reliable;
+pub(super) mod routing;
 mod sender;
+pub(super) mod total_order;
 
 use crate::{Error, Handler, Message};
 use std::io::{stdin, stdout, Read, Stdin, Stdout, Write};
-use tokio::task::JoinHandle;
+use tokio::{sync::mpsc::unbounded_channel, task::JoinHandle};
 
 pub struct Node<R, W, H> {
     input: R,
@@ -30,9 +35,17 @@ where
 
     pub fn start(self) -> JoinHandle<()> {
         let (handle, output_tx) = output::start(self.output);
-        let send_tx = sender::start(output_tx);
-        let input_rx = input::start(self.input);
-        handler::start(self.handler, input_rx, send_tx);
+        let callbacks = callbacks::Callbacks::default();
+        let send_tx = sender::start(output_tx, callbacks.clone());
+        let (message_tx, message_rx) = unbounded_channel();
+        handler::start(
+            self.handler,
+            message_rx,
+            message_tx.downgrade(),
+            send_tx,
+            callbacks,
+        );
+        input::start(self.input, message_tx);
         handle
     }
 }
@@ -0,0 +1,303 @@
+use crate::{rt::SendMessage, Message};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+use tokio::{
+    spawn,
+    sync::mpsc::WeakUnboundedSender,
+    time::{interval, Instant},
+};
+
+/// How long a reliable message may go unacknowledged before it is sent again.
+pub const RETRANSMIT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The message type the receiver acknowledges reliable messages with.
+pub const ACK: &str = "reliable_ack";
+
+/// A sequence of reliable messages from one node to another. Each stream
+/// numbers its messages separately, in the body field named by
+/// [field](Stream::field); acknowledgements carry the same field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Stream {
+    /// delivered as they arrive
+    Unordered,
+
+    /// delivered in the order they were sent
+    Fifo,
+}
+
+impl Stream {
+    pub fn field(self) -> &'static str {
+        match self {
+            Stream::Unordered => "reliable_seq",
+            Stream::Fifo => "fifo_seq",
+        }
+    }
+
+    /// The stream and sequence number of a reliable message or
+    /// acknowledgement, or `None` for any other message.
+    pub fn of(body: &Value) -> Option<(Stream, u64)> {
+        [Stream::Unordered, Stream::Fifo]
+            .into_iter()
+            .find_map(|stream| Some((stream, body[stream.field()].as_u64()?)))
+    }
+}
+
+/// Reliable messages sent but not yet acknowledged, by destination and
+/// stream. The sender numbers each message, keeps it until the destination
+/// acknowledges its sequence number, and sends it again every
+/// [RETRANSMIT_INTERVAL] until then.
+#[derive(Default)]
+pub struct Outbox {
+    destinations: HashMap<(String, Stream), Destination>,
+}
+
+#[derive(Default)]
+struct Destination {
+    last_seq: u64,
+
+    /// unacknowledged bodies, by sequence number, with when they were last
+    /// sent
+    unacked: BTreeMap<u64, (Value, Instant)>,
+}
+
+impl Outbox {
+    /// Numbers a message to `dest` and keeps it until acknowledged,
+    /// returning the body to send.
+    pub fn push(&mut self, dest: &str, stream: Stream, mut body: Value) -> Value {
+        let key = (dest.to_string(), stream);
+        let destination = self.destinations.entry(key).or_default();
+        destination.last_seq += 1;
+        body[stream.field()] = Value::from(destination.last_seq);
+
+        let entry = (body.clone(), Instant::now());
+        destination.unacked.insert(destination.last_seq, entry);
+        body
+    }
+
+    pub fn ack(&mut self, src: &str, stream: Stream, seq: u64) {
+        let key = (src.to_string(), stream);
+        if let Some(destination) = self.destinations.get_mut(&key) {
+            destination.unacked.remove(&seq);
+        }
+    }
+
+    /// The messages that have gone unacknowledged for a full
+    /// [RETRANSMIT_INTERVAL] since they were last sent, as `(dest, body)`
+    /// pairs. They count as sent again now.
+    pub fn overdue(&mut self) -> Vec<(String, Value)> {
+        let now = Instant::now();
+        let mut overdue = vec![];
+
+        for ((dest, _), destination) in self.destinations.iter_mut() {
+            for (body, sent) in destination.unacked.values_mut() {
+                if now.duration_since(*sent) >= RETRANSMIT_INTERVAL {
+                    *sent = now;
+                    overdue.push((dest.to_string(), body.clone()));
+                }
+            }
+        }
+
+        overdue
+    }
+}
+
+/// Periodically asks the sender to retransmit overdue messages, until the
+/// sender stops.
+pub fn start_retransmits(send_tx: WeakUnboundedSender<SendMessage>) {
+    spawn(async move {
+        let mut interval = interval(RETRANSMIT_INTERVAL);
+        loop {
+            interval.tick().await;
+            let sent = send_tx
+                .upgrade()
+                .is_some_and(|send_tx| send_tx.send(SendMessage::Retransmit).is_ok());
+            if !sent {
+                break;
+            }
+        }
+    });
+}
+
+/// The reliable messages received so far, by source and stream, so that
+/// retransmissions are delivered to the handler only once and [FIFO]
+/// messages are delivered in order.
+///
+/// [FIFO]: Stream::Fifo
+#[derive(Default)]
+pub struct Inbox {
+    sources: HashMap<(String, Stream), Received>,
+}
+
+#[derive(Default)]
+struct Received {
+    /// every message up to and including this sequence number was delivered
+    up_to: u64,
+
+    /// messages above `up_to` that were received: `None` if already
+    /// delivered, or the message if it is waiting for earlier ones
+    above: BTreeMap<u64, Option<Message>>,
+}
+
+impl Inbox {
+    /// Records a reliable message, returning the messages that are ready
+    /// for the handler: none if it is a retransmission, and for FIFO
+    /// streams, the run of messages it completes.
+    pub fn receive(&mut self, stream: Stream, seq: u64, message: Message) -> Vec<Message> {
+        let key = (message.src().to_string(), stream);
+        let received = self.sources.entry(key).or_default();
+        if seq <= received.up_to || received.above.contains_key(&seq) {
+            return vec![];
+        }
+
+        let mut ready = vec![];
+        match stream {
+            Stream::Unordered => {
+                received.above.insert(seq, None);
+                ready.push(message);
+            }
+            Stream::Fifo => _ = received.above.insert(seq, Some(message)),
+        }
+
+        while let Some(message) = received.above.remove(&(received.up_to + 1)) {
+            received.up_to += 1;
+            ready.extend(message);
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::Network, Context, Error, Handler, Message};
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+    use tokio::time::sleep;
+
+    /// Has `inbox` receive message `seq` from `src` on `stream`, returning
+    /// the sequence numbers of the messages it makes ready.
+    fn receive(inbox: &mut Inbox, src: &str, stream: Stream, seq: u64) -> Vec<u64> {
+        let body = json!({"type": "value", stream.field(): seq});
+        let json = json!({"src": src, "dest": "n9", "body": body});
+        let ready = inbox.receive(stream, seq, Message::from_json(json).unwrap());
+        ready
+            .iter()
+            .filter_map(|message| Some(Stream::of(message.body())?.1))
+            .collect()
+    }
+
+    #[test]
+    fn inbox_drops_duplicates() {
+        let mut inbox = Inbox::default();
+        let unordered = Stream::Unordered;
+        assert_eq!(receive(&mut inbox, "n0", unordered, 2), vec![2]);
+        assert_eq!(receive(&mut inbox, "n0", unordered, 1), vec![1]);
+        assert!(receive(&mut inbox, "n0", unordered, 2).is_empty());
+        assert!(receive(&mut inbox, "n0", unordered, 1).is_empty());
+        assert_eq!(receive(&mut inbox, "n1", unordered, 1), vec![1]);
+        assert_eq!(receive(&mut inbox, "n0", unordered, 3), vec![3]);
+    }
+
+    #[test]
+    fn inbox_delivers_fifo_streams_in_order() {
+        let mut inbox = Inbox::default();
+        let fifo = Stream::Fifo;
+        assert!(receive(&mut inbox, "n0", fifo, 3).is_empty());
+        assert!(receive(&mut inbox, "n0", fifo, 2).is_empty());
+        assert_eq!(receive(&mut inbox, "n0", Stream::Unordered, 1), vec![1]);
+        assert!(receive(&mut inbox, "n0", fifo, 3).is_empty());
+        assert_eq!(receive(&mut inbox, "n0", fifo, 1), vec![1, 2, 3]);
+        assert!(receive(&mut inbox, "n0", fifo, 2).is_empty());
+        assert_eq!(receive(&mut inbox, "n0", fifo, 4), vec![4]);
+    }
+
+    enum Command {
+        Send(u64),
+        SendFifo(u64),
+        Value(u64),
+    }
+
+    impl TryFrom<Message> for Command {
+        type Error = Error;
+
+        fn try_from(message: Message) -> Result<Self, Self::Error> {
+            let value = message.body()["value"].as_u64().unwrap_or_default();
+            match message.msg_type() {
+                "send" => Ok(Command::Send(value)),
+                "send_fifo" => Ok(Command::SendFifo(value)),
+                "value" => Ok(Command::Value(value)),
+                msg_type => Err(Error::not_supported(msg_type)),
+            }
+        }
+    }
+
+    /// Sends values from n0 to n1, which records every value it handles.
+    struct Pipe(Arc<Mutex<Vec<u64>>>);
+
+    impl Handler for Pipe {
+        type Command = Command;
+
+        fn handle(&mut self, command: Self::Command, ctx: Context) {
+            match command {
+                Command::Send(value) => {
+                    let body = json!({"type": "value", "value": value});
+                    ctx.send_reliable("n1".to_string(), body);
+                    ctx.reply(json!({"type": "send_ok"}));
+                }
+                Command::SendFifo(value) => {
+                    let body = json!({"type": "value", "value": value});
+                    ctx.send_fifo("n1".to_string(), body);
+                    ctx.reply(json!({"type": "send_ok"}));
+                }
+                Command::Value(value) => self.0.lock().unwrap().push(value),
+            }
+        }
+    }
+
+    /// Has n0 send 0..5 to n1 with requests of type `send` while n1 is
+    /// isolated, then heals the partition and returns the values n1 handled.
+    async fn send_through_partition(send: &str) -> Vec<u64> {
+        let network = Network::new();
+        let received = Arc::new(Mutex::new(vec![]));
+        network.start("n0", Pipe(received.clone()));
+        network.start("n1", Pipe(received.clone()));
+        network.init(&["n0", "n1"]).await;
+
+        network.isolate("n1");
+        for value in 0..5 {
+            network
+                .request("n0", json!({"type": send, "value": value}))
+                .await;
+        }
+        sleep(RETRANSMIT_INTERVAL * 3).await;
+        assert!(received.lock().unwrap().is_empty());
+
+        network.heal();
+        for _ in 0..50 {
+            if received.lock().unwrap().len() == 5 {
+                break;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+        sleep(RETRANSMIT_INTERVAL * 3).await;
+
+        let received = received.lock().unwrap().clone();
+        received
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn delivers_once_through_partitions() {
+        let mut received = send_through_partition("send").await;
+        received.sort();
+        assert_eq!(received, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn delivers_fifo_in_send_order() {
+        let received = send_through_partition("send_fifo").await;
+        assert_eq!(received, vec![0, 1, 2, 3, 4]);
+    }
+}
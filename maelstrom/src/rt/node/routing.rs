@@ -0,0 +1,165 @@
+use crate::{
+    rt::{Cluster, SendMessage},
+    Message, Topology,
+};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// The body field carrying a routed message's final destination.
+pub const DEST: &str = "route_dest";
+
+/// The body field carrying the node a routed message came from.
+const ORIGIN: &str = "route_origin";
+
+/// The next hop towards each node, by destination, shared by the contexts
+/// that send routed messages and the handler loop that forwards them. Empty
+/// until a topology is set.
+#[derive(Clone, Default)]
+pub struct Routes(Arc<Mutex<HashMap<String, String>>>);
+
+impl Routes {
+    pub fn set(&self, topology: &Topology, node_id: &str) {
+        *self.0.lock().unwrap() = topology.next_hops(node_id);
+    }
+
+    /// Wraps a message from `origin` to `dest` for routing, returning the
+    /// node to send it to and the body to send. Without a known route, the
+    /// message is sent to `dest` directly.
+    pub fn route(&self, origin: &str, dest: String, mut body: Value) -> (String, Value) {
+        let Some(hop) = self.0.lock().unwrap().get(&dest).cloned() else {
+            return (dest, body);
+        };
+
+        body[DEST] = Value::from(dest);
+        body[ORIGIN] = Value::from(origin);
+        (hop, body)
+    }
+
+    /// Forwards routed messages that are addressed to another node,
+    /// returning the message to handle, if any. Routed messages addressed to
+    /// this node appear to come from the node that sent them.
+    pub fn receive(
+        &self,
+        message: Message,
+        cluster: &Cluster,
+        send_tx: &UnboundedSender<SendMessage>,
+    ) -> Option<Message> {
+        let dest = match message.body()[DEST].as_str() {
+            Some(dest) if dest != cluster.node_id => dest.to_string(),
+            Some(_) => {
+                let mut json = Value::from(message);
+                json["src"] = json["body"][ORIGIN].clone();
+                return Message::from_json(json).ok();
+            }
+            None => return Some(message),
+        };
+
+        match self.0.lock().unwrap().get(&dest) {
+            Some(hop) => {
+                let forward = SendMessage::send(hop.to_string(), None, message.body().clone());
+                let _ = send_tx.send(forward);
+            }
+            None => eprintln!("dropping message to {}: no route", dest),
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::Network, Context, Error, Handler, Message, Topology};
+    use serde_json::json;
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    use tokio::time::sleep;
+
+    enum Command {
+        Send(String),
+        Value,
+    }
+
+    impl TryFrom<Message> for Command {
+        type Error = Error;
+
+        fn try_from(message: Message) -> Result<Self, Self::Error> {
+            match message.msg_type() {
+                "send" => {
+                    let dest = message.body()["to"].as_str().unwrap_or_default();
+                    Ok(Command::Send(dest.to_string()))
+                }
+                "value" => Ok(Command::Value),
+                msg_type => Err(Error::not_supported(msg_type)),
+            }
+        }
+    }
+
+    /// Routes over the line n0 - n1 - n2, recording the nodes values are
+    /// received from.
+    struct Line(Arc<Mutex<Vec<String>>>);
+
+    impl Handler for Line {
+        type Command = Command;
+
+        fn init(&mut self, ctx: Context) {
+            let topology = Topology::try_from(&json!({
+                "n0": ["n1"],
+                "n1": ["n0", "n2"],
+                "n2": ["n1"],
+            }));
+            ctx.set_topology(&topology.unwrap());
+        }
+
+        fn handle(&mut self, command: Self::Command, ctx: Context) {
+            match command {
+                Command::Send(dest) => {
+                    ctx.send_routed(dest, json!({"type": "value"}));
+                    ctx.reply(json!({"type": "send_ok"}));
+                }
+                Command::Value => self.0.lock().unwrap().push(ctx.src().to_string()),
+            }
+        }
+    }
+
+    async fn wait_for(received: &Mutex<Vec<String>>, count: usize) {
+        for _ in 0..50 {
+            if received.lock().unwrap().len() >= count {
+                return;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn forwards_through_intermediate_nodes() {
+        let network = Network::new();
+        let received = Arc::new(Mutex::new(vec![]));
+        let nodes = ["n0", "n1", "n2"];
+        for node_id in nodes {
+            network.start(node_id, Line(received.clone()));
+        }
+        network.init(&nodes).await;
+
+        network
+            .request("n0", json!({"type": "send", "to": "n2"}))
+            .await;
+        wait_for(&received, 1).await;
+        assert_eq!(*received.lock().unwrap(), ["n0"]);
+
+        // with n1 gone, n2 is unreachable from n0
+        network.stop("n1");
+        network
+            .request("n0", json!({"type": "send", "to": "n2"}))
+            .await;
+        network
+            .request("n2", json!({"type": "send", "to": "n0"}))
+            .await;
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(*received.lock().unwrap(), ["n0"]);
+    }
+}
@@ -1,3 +1,4 @@
+use super::{callbacks::Callbacks, reliable};
 use crate::rt::SendMessage;
 use serde_json::{json, Value};
 use std::sync::mpsc::Sender;
@@ -6,48 +7,73 @@ use tokio::{
     sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
 };
 
-pub fn start(output_tx: Sender<Value>) -> UnboundedSender<SendMessage> {
+pub fn start(output_tx: Sender<Value>, callbacks: Callbacks) -> UnboundedSender<SendMessage> {
     let (send_tx, send_rx) = unbounded_channel();
-    spawn(async move { send(send_rx, output_tx).await });
+    reliable::start_retransmits(send_tx.downgrade());
+    spawn(async move { send(send_rx, output_tx, callbacks).await });
     send_tx
 }
 
-async fn send(mut send_rx: UnboundedReceiver<SendMessage>, output_tx: Sender<Value>) {
+async fn send(
+    mut send_rx: UnboundedReceiver<SendMessage>,
+    output_tx: Sender<Value>,
+    callbacks: Callbacks,
+) {
     let mut node_id: Option<String> = None;
     let mut last_msg_id: u64 = 0;
+    let mut outbox = reliable::Outbox::default();
 
-    loop {
-        let reply = send_rx.recv().await;
-        let (dest, in_reply_to, mut body) = match reply {
-            Some(SendMessage::SetNodeId {
+    while let Some(reply) = send_rx.recv().await {
+        let messages = match reply {
+            SendMessage::SetNodeId {
                 dest,
                 in_reply_to,
                 node_id: new_node_id,
-            }) => {
+            } => {
                 let _ = node_id.insert(new_node_id);
                 let body = json!({"type": "init_ok"});
-                (dest, in_reply_to, body)
+                vec![(dest, in_reply_to, body)]
             }
-            Some(SendMessage::Send {
+            SendMessage::Send {
                 dest,
                 in_reply_to,
                 body,
-            }) => (dest, in_reply_to, body),
-            None => {
-                break;
+            } => vec![(dest, in_reply_to, body)],
+            SendMessage::Rpc {
+                dest,
+                body,
+                reply_tx,
+            } => {
+                callbacks.register(last_msg_id + 1, reply_tx);
+                vec![(dest, None, body)]
+            }
+            SendMessage::Reliable { dest, stream, body } => {
+                let body = outbox.push(&dest, stream, body);
+                vec![(dest, None, body)]
+            }
+            SendMessage::Acked { src, stream, seq } => {
+                outbox.ack(&src, stream, seq);
+                vec![]
             }
+            SendMessage::Retransmit => outbox
+                .overdue()
+                .into_iter()
+                .map(|(dest, body)| (dest, None, body))
+                .collect(),
         };
 
-        last_msg_id += 1;
-        body["msg_id"] = Value::from(last_msg_id);
-        body["in_reply_to"] = Value::from(in_reply_to);
+        for (dest, in_reply_to, mut body) in messages {
+            last_msg_id += 1;
+            body["msg_id"] = Value::from(last_msg_id);
+            body["in_reply_to"] = Value::from(in_reply_to);
 
-        let message = json!({
-            "src": node_id,
-            "dest": dest,
-            "body": body
-        });
+            let message = json!({
+                "src": node_id,
+                "dest": dest,
+                "body": body
+            });
 
-        let _ = output_tx.send(message);
+            let _ = output_tx.send(message);
+        }
     }
 }
@@ -0,0 +1,169 @@
+use super::reliable::Stream;
+use crate::{
+    rt::{Cluster, SendMessage},
+    Message,
+};
+use serde_json::Value;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// The body field marking a total-order broadcast, holding [SUBMIT] on its
+/// way to the sequencer and [DELIVER] on its way from it.
+pub const STAGE: &str = "total_order";
+pub const SUBMIT: &str = "submit";
+pub const DELIVER: &str = "deliver";
+
+/// The body field carrying the node a sequenced broadcast came from.
+const ORIGIN: &str = "total_order_origin";
+
+/// The node that orders all broadcasts: the one with the smallest id, so
+/// that every node agrees on it without communicating.
+pub fn sequencer(cluster: &Cluster) -> Option<&str> {
+    cluster.node_ids.iter().min().map(String::as_str)
+}
+
+/// Routes total-order broadcasts through the sequencer, returning the
+/// message to handle, if any.
+///
+/// The sequencer forwards each broadcast submitted to it to every other node
+/// on a FIFO stream, in the order it receives them, and hands it to its own
+/// handler at the same point; since each node delivers the sequencer's FIFO
+/// stream in order, all nodes deliver the same broadcasts in the same order.
+/// Delivered broadcasts appear to come from the node that submitted them.
+/// Any other message is returned as is.
+pub fn receive(
+    message: Message,
+    cluster: &Cluster,
+    send_tx: &UnboundedSender<SendMessage>,
+) -> Option<Message> {
+    match message.body()[STAGE].as_str() {
+        Some(SUBMIT) if sequencer(cluster) == Some(&cluster.node_id) => {
+            let mut body = message.body().clone();
+            if let Some(fields) = body.as_object_mut() {
+                fields.remove(Stream::Unordered.field());
+                fields.remove(Stream::Fifo.field());
+            }
+            body[STAGE] = Value::from(DELIVER);
+            body[ORIGIN] = Value::from(message.src());
+
+            for dest in cluster.node_ids.iter().filter(|id| **id != cluster.node_id) {
+                let deliver = SendMessage::reliable(dest.to_string(), Stream::Fifo, body.clone());
+                let _ = send_tx.send(deliver);
+            }
+            from_origin(message, body)
+        }
+        Some(SUBMIT) => {
+            eprintln!("dropping broadcast submitted to a node that isn't the sequencer");
+            None
+        }
+        Some(DELIVER) => {
+            let body = message.body().clone();
+            from_origin(message, body)
+        }
+        _ => Some(message),
+    }
+}
+
+/// Rebuilds a sequenced broadcast with `body`, as sent by its origin.
+fn from_origin(message: Message, body: Value) -> Option<Message> {
+    let mut json = Value::from(message);
+    json["src"] = body[ORIGIN].clone();
+    json["body"] = body;
+    Message::from_json(json).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::Network, Context, Error, Handler, Message};
+    use serde_json::json;
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    use tokio::time::sleep;
+
+    enum Command {
+        Broadcast(u64),
+        Value(u64),
+    }
+
+    impl TryFrom<Message> for Command {
+        type Error = Error;
+
+        fn try_from(message: Message) -> Result<Self, Self::Error> {
+            let value = message.body()["value"].as_u64().unwrap_or_default();
+            match message.msg_type() {
+                "broadcast" => Ok(Command::Broadcast(value)),
+                "value" => Ok(Command::Value(value)),
+                msg_type => Err(Error::not_supported(msg_type)),
+            }
+        }
+    }
+
+    /// The values each node handled, in order, by node id.
+    type Log = Arc<Mutex<HashMap<String, Vec<(String, u64)>>>>;
+
+    struct Recorder(Log);
+
+    impl Handler for Recorder {
+        type Command = Command;
+
+        fn handle(&mut self, command: Self::Command, ctx: Context) {
+            match command {
+                Command::Broadcast(value) => {
+                    ctx.broadcast_total(json!({"type": "value", "value": value}));
+                    ctx.reply(json!({"type": "broadcast_ok"}));
+                }
+                Command::Value(value) => {
+                    let mut log = self.0.lock().unwrap();
+                    let entry = (ctx.src().to_string(), value);
+                    log.entry(ctx.node_id().to_string())
+                        .or_default()
+                        .push(entry);
+                }
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn delivers_in_the_same_order_everywhere() {
+        let network = Network::new();
+        let log = Log::default();
+        let nodes = ["n0", "n1", "n2"];
+        for node_id in nodes {
+            network.start(node_id, Recorder(log.clone()));
+        }
+        network.init(&nodes).await;
+
+        for value in 0..12 {
+            if value == 4 {
+                network.isolate("n2");
+            }
+            let body = json!({"type": "broadcast", "value": value});
+            network.request(nodes[value as usize % 3], body).await;
+        }
+        network.heal();
+
+        let complete = || {
+            let log = log.lock().unwrap();
+            nodes
+                .iter()
+                .all(|node_id| log.get(*node_id).is_some_and(|values| values.len() == 12))
+        };
+        for _ in 0..50 {
+            if complete() {
+                break;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+
+        let log = log.lock().unwrap();
+        let order = &log["n0"];
+        assert_eq!(order.len(), 12);
+        assert_eq!(log["n1"], *order);
+        assert_eq!(log["n2"], *order);
+        for (src, value) in order {
+            assert_eq!(*src, nodes[*value as usize % 3]);
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use crate::Error;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// A graph of nodes, as sent in the `topology` field of the broadcast
+/// workload's `topology` message: each node's neighbors, by node id.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Topology {
+    neighbors: HashMap<String, Vec<String>>,
+}
+
+impl Topology {
+    /// A tree in which each of `node_ids` has up to `arity` children, filled
+    /// in order from the first node, the root.
+    pub fn tree(node_ids: &[String], arity: usize) -> Self {
+        let edges = (1..node_ids.len()).map(|child| ((child - 1) / arity.max(1), child));
+        Self::from_edges(node_ids, edges)
+    }
+
+    /// A grid as close to square as possible, filled row by row, in which
+    /// each node is connected to the nodes next to it, above and below.
+    pub fn grid(node_ids: &[String]) -> Self {
+        let width = (1..)
+            .find(|width| width * width >= node_ids.len())
+            .unwrap_or(1);
+        let edges = (0..node_ids.len()).flat_map(|node| {
+            let right = (node % width + 1 < width).then_some((node, node + 1));
+            let below = Some((node, node + width));
+            right.into_iter().chain(below)
+        });
+        let edges = edges.filter(|(_, other)| *other < node_ids.len());
+        Self::from_edges(node_ids, edges)
+    }
+
+    /// A star with the first of `node_ids` as its hub.
+    pub fn star(node_ids: &[String]) -> Self {
+        Self::from_edges(node_ids, (1..node_ids.len()).map(|node| (0, node)))
+    }
+
+    /// A breadth-first spanning tree of this graph, rooted at `root`. Nodes
+    /// unreachable from the root are left without neighbors.
+    pub fn spanning_tree(&self, root: &str) -> Self {
+        let mut tree = Self::default();
+        let mut visited = HashSet::from([root]);
+        let mut queue = VecDeque::from([root]);
+        tree.neighbors.insert(root.to_string(), vec![]);
+
+        while let Some(node_id) = queue.pop_front() {
+            for neighbor in self.neighbors(node_id) {
+                if visited.insert(neighbor) {
+                    tree.connect(node_id, neighbor);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        for node_id in self.neighbors.keys() {
+            tree.neighbors.entry(node_id.to_string()).or_default();
+        }
+        tree
+    }
+
+    /// The neighbors of `node_id`, or none if it isn't in the graph.
+    pub fn neighbors(&self, node_id: &str) -> &[String] {
+        self.neighbors.get(node_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// The neighbor of `from` on a shortest path to each node reachable from
+    /// it, by destination.
+    pub fn next_hops(&self, from: &str) -> HashMap<String, String> {
+        let mut next_hops = HashMap::new();
+        let mut visited = HashSet::from([from]);
+        let mut queue = VecDeque::new();
+
+        for neighbor in self.neighbors(from) {
+            if visited.insert(neighbor) {
+                next_hops.insert(neighbor.to_string(), neighbor.to_string());
+                queue.push_back(neighbor);
+            }
+        }
+
+        while let Some(node_id) = queue.pop_front() {
+            let hop = next_hops[node_id.as_str()].clone();
+            for neighbor in self.neighbors(node_id) {
+                if visited.insert(neighbor) {
+                    next_hops.insert(neighbor.to_string(), hop.clone());
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        next_hops
+    }
+
+    /// A graph of `node_ids` with undirected edges between the nodes at the
+    /// given indices.
+    fn from_edges(node_ids: &[String], edges: impl Iterator<Item = (usize, usize)>) -> Self {
+        let neighbors = node_ids.iter().map(|id| (id.to_string(), vec![]));
+        let mut topology = Self {
+            neighbors: neighbors.collect(),
+        };
+        for (a, b) in edges {
+            topology.connect(&node_ids[a], &node_ids[b]);
+        }
+        topology
+    }
+
+    fn connect(&mut self, a: &str, b: &str) {
+        self.neighbors
+            .entry(a.to_string())
+            .or_default()
+            .push(b.to_string());
+        self.neighbors
+            .entry(b.to_string())
+            .or_default()
+            .push(a.to_string());
+    }
+}
+
+impl From<HashMap<String, Vec<String>>> for Topology {
+    fn from(neighbors: HashMap<String, Vec<String>>) -> Self {
+        Self { neighbors }
+    }
+}
+
+impl TryFrom<&Value> for Topology {
+    type Error = Error;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let invalid = || Error::malformed_request("topology must map node ids to node id arrays");
+        let mut neighbors = HashMap::new();
+
+        for (node_id, ids) in value.as_object().ok_or_else(invalid)? {
+            let ids = ids
+                .as_array()
+                .ok_or_else(invalid)?
+                .iter()
+                .map(|id| id.as_str().map(String::from))
+                .collect::<Option<_>>()
+                .ok_or_else(invalid)?;
+            neighbors.insert(node_id.to_string(), ids);
+        }
+
+        Ok(Self { neighbors })
+    }
+}
+
+impl From<&Topology> for Value {
+    fn from(topology: &Topology) -> Self {
+        topology
+            .neighbors
+            .iter()
+            .map(|(node_id, ids)| (node_id.to_string(), Value::from(ids.clone())))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn routes_through_shortest_paths() {
+        let topology = Topology::try_from(&json!({
+            "n0": ["n1"],
+            "n1": ["n0", "n2", "n3"],
+            "n2": ["n1", "n4"],
+            "n3": ["n1", "n4"],
+            "n4": ["n2", "n3"],
+            "n5": [],
+        }))
+        .unwrap();
+
+        let next_hops = topology.next_hops("n2");
+        assert_eq!(next_hops["n0"], "n1");
+        assert_eq!(next_hops["n3"], "n1");
+        assert_eq!(next_hops["n4"], "n4");
+        assert!(!next_hops.contains_key("n2"));
+        assert!(!next_hops.contains_key("n5"));
+
+        assert!(Topology::try_from(&json!({"n0": "n1"})).is_err());
+    }
+
+    fn ids(count: usize) -> Vec<String> {
+        (0..count).map(|node| format!("n{}", node)).collect()
+    }
+
+    #[test]
+    fn builds_overlays() {
+        let tree = Topology::tree(&ids(7), 2);
+        assert_eq!(tree.neighbors("n0"), ["n1", "n2"]);
+        assert_eq!(tree.neighbors("n2"), ["n0", "n5", "n6"]);
+        assert_eq!(tree.neighbors("n6"), ["n2"]);
+
+        // n0 n1 n2
+        // n3 n4
+        let grid = Topology::grid(&ids(5));
+        assert_eq!(grid.neighbors("n0"), ["n1", "n3"]);
+        assert_eq!(grid.neighbors("n2"), ["n1"]);
+        assert_eq!(grid.neighbors("n4"), ["n1", "n3"]);
+
+        let star = Topology::star(&ids(4));
+        assert_eq!(star.neighbors("n0"), ["n1", "n2", "n3"]);
+        assert_eq!(star.neighbors("n3"), ["n0"]);
+
+        let ring = Topology::try_from(&json!({
+            "n0": ["n1", "n3"],
+            "n1": ["n0", "n2"],
+            "n2": ["n1", "n3"],
+            "n3": ["n2", "n0"],
+        }))
+        .unwrap();
+        let tree = ring.spanning_tree("n0");
+        assert_eq!(tree.neighbors("n0"), ["n1", "n3"]);
+        assert_eq!(tree.neighbors("n1"), ["n0", "n2"]);
+        assert_eq!(tree.neighbors("n2"), ["n1"]);
+        assert_eq!(tree.neighbors("n3"), ["n0"]);
+    }
+}
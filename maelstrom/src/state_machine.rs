@@ -0,0 +1,26 @@
+use crate::Error;
+use serde_json::Value;
+
+/// A deterministic state machine, replicated by a consensus protocol such as
+/// the ones in the `raft` and `paxos` crates.
+///
+/// Every node applies the same commands in the same order, so applying a
+/// command must depend only on the current state and the command itself.
+pub trait StateMachine {
+    /// The commands applied to this state machine. Commands are parsed from
+    /// client request bodies and travel between nodes in their JSON form,
+    /// so converting a command into a [Value] and back must round-trip.
+    type Command: TryFrom<Value, Error = Error> + Into<Value> + Clone;
+
+    /// Applies a committed command, returning the body of the reply to the
+    /// client that issued it.
+    fn apply(&mut self, command: &Self::Command) -> Value;
+
+    /// Captures the current state, for log compaction.
+    fn snapshot(&self) -> Value;
+
+    /// Replaces the current state with one captured by [snapshot].
+    ///
+    /// [snapshot]: StateMachine::snapshot
+    fn restore(&mut self, snapshot: Value);
+}
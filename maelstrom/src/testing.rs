@@ -0,0 +1,223 @@
+//! An in-process Maelstrom network for testing handlers.
+//!
+//! A [Network] runs each node on the current tokio runtime with the regular
+//! [Node] runtime, connected through pipes instead of stdin/stdout. It routes
+//! messages between nodes, emulates the `lin-kv`, `seq-kv` and `lww-kv`
+//! services (all three linearizably), lets tests act as clients, and can
+//! partition, stop and restart nodes.
+//!
+//! Handlers that spawn blocking work need a multi-threaded runtime, so tests
+//! should use `#[tokio::test(flavor = "multi_thread")]`.
+pub mod counter;
+
+use crate::{Error, Handler, Message, Node};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, pipe, PipeWriter, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{sync::oneshot, time::timeout};
+
+/// The client id used for requests issued by the test.
+const CLIENT: &str = "c1";
+
+#[derive(Default)]
+struct State {
+    /// the input pipe of each running node
+    inputs: HashMap<String, PipeWriter>,
+
+    /// node ids that can't exchange messages with any other node
+    isolated: HashSet<String>,
+
+    /// key/value services, by service name and key
+    services: HashMap<String, Map<String, Value>>,
+
+    /// requests issued by the test waiting for a reply, by `msg_id`
+    waiting: HashMap<u64, oneshot::Sender<Value>>,
+
+    next_msg_id: u64,
+}
+
+/// An in-process network of nodes. See the [module docs](self).
+#[derive(Clone, Default)]
+pub struct Network {
+    state: Arc<Mutex<State>>,
+}
+
+impl Network {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a node with the given id. The node is not initialized until
+    /// [init](Network::init) is called. If a node with the same id was
+    /// running, it must have been [stopped](Network::stop) first.
+    pub fn start<H, C>(&self, node_id: &str, handler: H)
+    where
+        H: Handler<Command = C> + Send + 'static,
+        C: TryFrom<Message, Error = Error> + Send,
+    {
+        let (input, input_tx) = pipe().expect("failed to create node input pipe");
+        let output = Output {
+            network: self.clone(),
+            buffer: vec![],
+        };
+
+        self.lock().inputs.insert(node_id.to_string(), input_tx);
+        Node::new(input, output, handler).start();
+    }
+
+    /// Sends an `init` message to each of `node_ids`, announcing all of them
+    /// as the cluster, and waits for every node to reply.
+    pub async fn init(&self, node_ids: &[&str]) {
+        for node_id in node_ids {
+            self.init_node(node_id, node_ids).await;
+        }
+    }
+
+    /// Sends an `init` message to a single node, e.g. after restarting it.
+    pub async fn init_node(&self, node_id: &str, node_ids: &[&str]) {
+        let body = json!({"type": "init", "node_id": node_id, "node_ids": node_ids});
+        let reply = self.request(node_id, body).await;
+        assert_eq!(reply["type"], "init_ok", "node {} failed to init", node_id);
+    }
+
+    /// Sends a request from a client to a node and waits for its reply.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node doesn't reply within 5 seconds.
+    pub async fn request(&self, node_id: &str, body: Value) -> Value {
+        self.try_request(node_id, body, Duration::from_secs(5))
+            .await
+            .unwrap_or_else(|| panic!("no reply from {}", node_id))
+    }
+
+    /// Sends a request from a client to a node and waits up to `duration` for
+    /// its reply.
+    pub async fn try_request(
+        &self,
+        node_id: &str,
+        body: Value,
+        duration: Duration,
+    ) -> Option<Value> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+
+        {
+            let mut state = self.lock();
+            state.next_msg_id += 1;
+            let msg_id = state.next_msg_id;
+            state.waiting.insert(msg_id, reply_tx);
+
+            let mut body = body;
+            body["msg_id"] = msg_id.into();
+            let message = json!({"src": CLIENT, "dest": node_id, "body": body});
+            state.deliver(node_id, &message);
+        }
+
+        timeout(duration, reply_rx).await.ok()?.ok()
+    }
+
+    /// Stops a node by closing its input.
+    pub fn stop(&self, node_id: &str) {
+        self.lock().inputs.remove(node_id);
+    }
+
+    /// Cuts a node off from all other nodes. Clients and services can still
+    /// reach it.
+    pub fn isolate(&self, node_id: &str) {
+        self.lock().isolated.insert(node_id.to_string());
+    }
+
+    /// Heals all partitions.
+    pub fn heal(&self) {
+        self.lock().isolated.clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    fn route(&self, message: Value) {
+        let mut state = self.lock();
+        let (src, dest) = (message["src"].as_str(), message["dest"].as_str());
+        let (Some(src), Some(dest)) = (src, dest) else {
+            return;
+        };
+
+        if state.inputs.contains_key(dest) {
+            let cut = state.isolated.contains(src) || state.isolated.contains(dest);
+            if !cut && state.inputs.contains_key(src) {
+                state.deliver(dest, &message);
+            }
+        } else if dest.ends_with("-kv") {
+            let reply = state.serve(dest, &message["body"]);
+            let message = json!({"src": dest, "dest": src, "body": reply});
+            state.deliver(src, &message);
+        } else if let Some(in_reply_to) = message["body"]["in_reply_to"].as_u64() {
+            if let Some(reply_tx) = state.waiting.remove(&in_reply_to) {
+                let _ = reply_tx.send(message["body"].clone());
+            }
+        }
+    }
+}
+
+impl State {
+    fn deliver(&mut self, node_id: &str, message: &Value) {
+        if let Some(input) = self.inputs.get_mut(node_id) {
+            let _ = writeln!(input, "{}", message);
+        }
+    }
+
+    fn serve(&mut self, service: &str, body: &Value) -> Value {
+        let data = self.services.entry(service.to_string()).or_default();
+        let key = body["key"].to_string();
+
+        let mut reply = match (body["type"].as_str(), data.get(&key)) {
+            (Some("read"), Some(value)) => json!({"type": "read_ok", "value": value}),
+            (Some("write"), _) => {
+                data.insert(key, body["value"].clone());
+                json!({"type": "write_ok"})
+            }
+            (Some("cas"), Some(value)) if *value == body["from"] => {
+                data.insert(key, body["to"].clone());
+                json!({"type": "cas_ok"})
+            }
+            (Some("cas"), Some(_)) => Error::precondition_failed("unexpected value").into(),
+            (Some("cas"), None) if body["create_if_not_exists"] == true => {
+                data.insert(key, body["to"].clone());
+                json!({"type": "cas_ok"})
+            }
+            (Some("read" | "cas"), None) => Error::key_does_not_exist("no such key").into(),
+            (msg_type, _) => Error::not_supported(msg_type.unwrap_or_default()).into(),
+        };
+
+        reply["in_reply_to"] = body["msg_id"].clone();
+        reply
+    }
+}
+
+/// A node's output: buffers written bytes and routes each complete line.
+struct Output {
+    network: Network,
+    buffer: Vec<u8>,
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line = self.buffer.drain(..=end).collect::<Vec<_>>();
+            if let Ok(message) = serde_json::from_slice(&line) {
+                self.network.route(message);
+            }
+        }
+        Ok(())
+    }
+}
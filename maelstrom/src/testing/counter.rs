@@ -0,0 +1,119 @@
+//! A replicated counter for testing consensus protocols that drive a
+//! [StateMachine], such as the ones in the `raft` and `paxos` crates.
+//!
+//! Clients send `{"type": "add", "delta": n}` and get back the counter's
+//! value after the addition. Each [Counter] shares its value with its
+//! clones, so a test can keep one to observe a node's state while the node
+//! owns another.
+use super::Network;
+use crate::{Error, Handler, Message, StateMachine};
+use serde_json::{json, Value};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::time::sleep;
+
+/// The node ids of the cluster started by [cluster].
+pub const NODES: [&str; 3] = ["n0", "n1", "n2"];
+
+/// Adds `delta` to the counter.
+#[derive(Clone)]
+pub struct Add(pub u64);
+
+impl TryFrom<Value> for Add {
+    type Error = Error;
+
+    fn try_from(body: Value) -> Result<Self, Self::Error> {
+        match (body["type"].as_str(), body["delta"].as_u64()) {
+            (Some("add"), Some(delta)) => Ok(Add(delta)),
+            _ => Err(Error::not_supported("unknown")),
+        }
+    }
+}
+
+impl From<Add> for Value {
+    fn from(add: Add) -> Self {
+        json!({"type": "add", "delta": add.0})
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Counter(Arc<AtomicU64>);
+
+impl Counter {
+    pub fn value(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+impl StateMachine for Counter {
+    type Command = Add;
+
+    fn apply(&mut self, command: &Add) -> Value {
+        let value = self.0.fetch_add(command.0, Ordering::SeqCst) + command.0;
+        json!({"type": "add_ok", "value": value})
+    }
+
+    fn snapshot(&self) -> Value {
+        self.value().into()
+    }
+
+    fn restore(&mut self, snapshot: Value) {
+        self.0.store(snapshot.as_u64().unwrap(), Ordering::SeqCst);
+    }
+}
+
+/// Starts and initializes [NODES], each running the handler `node` builds
+/// around its counter, and returns the counters in the same order.
+pub async fn cluster<H, C>(node: impl Fn(Counter) -> H) -> (Network, Vec<Counter>)
+where
+    H: Handler<Command = C> + Send + 'static,
+    C: TryFrom<Message, Error = Error> + Send,
+{
+    let network = Network::new();
+    let mut counters = vec![];
+    for node_id in NODES {
+        let counter = Counter::default();
+        network.start(node_id, node(counter.clone()));
+        counters.push(counter);
+    }
+
+    network.init(&NODES).await;
+    (network, counters)
+}
+
+/// Adds `delta` through `node_id`, retrying until the cluster accepts it.
+pub async fn add(network: &Network, node_id: &str, delta: u64) -> u64 {
+    loop {
+        let body = json!({"type": "add", "delta": delta});
+        let reply = network
+            .try_request(node_id, body, Duration::from_secs(1))
+            .await;
+
+        match reply {
+            Some(reply) if reply["type"] == "add_ok" => return reply["value"].as_u64().unwrap(),
+            _ => sleep(Duration::from_millis(50)).await,
+        }
+    }
+}
+
+/// Waits for every counter to reach `expected`.
+pub async fn converge(counters: &[Counter], expected: u64) {
+    for _ in 0..100 {
+        if counters.iter().all(|c| c.value() == expected) {
+            return;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+
+    let values = counters.iter().map(Counter::value);
+    panic!(
+        "expected {}, got {:?}",
+        expected,
+        values.collect::<Vec<_>>()
+    );
+}
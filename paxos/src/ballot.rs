@@ -0,0 +1,71 @@
+use maelstrom::Error;
+use serde_json::{json, Value};
+
+/// A Paxos ballot number. Ballots are totally ordered by round, with ties
+/// broken by the id of the proposing node, so no two nodes ever use the same
+/// ballot.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Ballot {
+    pub round: u64,
+    pub node_id: String,
+}
+
+impl Ballot {
+    /// The smallest ballot owned by `node_id` that is greater than `self`.
+    pub fn next(&self, node_id: &str) -> Self {
+        Self {
+            round: self.round + 1,
+            node_id: node_id.to_string(),
+        }
+    }
+}
+
+impl TryFrom<&Value> for Ballot {
+    type Error = Error;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match (value[0].as_u64(), value[1].as_str()) {
+            (Some(round), Some(node_id)) => Ok(Self {
+                round,
+                node_id: node_id.to_string(),
+            }),
+            _ => Err(Error::malformed_request(&format!(
+                "invalid ballot: {}",
+                value
+            ))),
+        }
+    }
+}
+
+impl From<&Ballot> for Value {
+    fn from(ballot: &Ballot) -> Self {
+        json!([ballot.round, ballot.node_id])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordered_by_round_then_node() {
+        let ballot = Ballot::default().next("n2");
+
+        assert!(ballot > Ballot::default());
+        assert!(ballot.next("n0") > ballot);
+        assert!(
+            ballot
+                < Ballot {
+                    round: 1,
+                    node_id: "n3".to_string()
+                }
+        );
+    }
+
+    #[test]
+    fn round_trips_through_json() -> Result<(), Error> {
+        let ballot = Ballot::default().next("n1");
+        assert_eq!(Ballot::try_from(&Value::from(&ballot))?, ballot);
+        Ok(())
+    }
+}
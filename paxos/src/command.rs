@@ -0,0 +1,195 @@
+use crate::ballot::Ballot;
+use maelstrom::{Error, Message};
+use serde_json::{json, Value};
+
+/// A value proposed for a log slot: a client command, or a no-op used by a
+/// new leader to fill slots nobody is known to have accepted anything for.
+/// Each proposal has a unique id so the node that received the client
+/// request can recognize it once chosen, whoever ends up proposing it.
+#[derive(Clone, Debug)]
+pub struct Proposal<C> {
+    pub id: String,
+    pub command: Option<C>,
+}
+
+impl<C> TryFrom<&Value> for Proposal<C>
+where
+    C: TryFrom<Value, Error = Error>,
+{
+    type Error = Error;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let Some(id) = value["id"].as_str() else {
+            return Err(Error::malformed_request("proposal missing `id` key"));
+        };
+
+        let command = match &value["command"] {
+            Value::Null => None,
+            command => Some(C::try_from(command.clone())?),
+        };
+
+        Ok(Self {
+            id: id.to_string(),
+            command,
+        })
+    }
+}
+
+impl<C> From<&Proposal<C>> for Value
+where
+    C: Into<Value> + Clone,
+{
+    fn from(proposal: &Proposal<C>) -> Self {
+        let command = proposal.command.clone().map(Into::into);
+        json!({"id": proposal.id, "command": command})
+    }
+}
+
+/// The commands processed by [Paxos](crate::Paxos): client requests for the
+/// state machine, Paxos' own messages, and timer ticks.
+pub enum Command<C> {
+    Client(C),
+    Prepare {
+        ballot: Ballot,
+        from_slot: u64,
+    },
+    Promise {
+        ballot: Ballot,
+        accepted: Vec<(u64, Ballot, Proposal<C>)>,
+    },
+    Accept {
+        ballot: Ballot,
+        slot: u64,
+        proposal: Proposal<C>,
+    },
+    Accepted {
+        ballot: Ballot,
+        slot: u64,
+    },
+    Nack {
+        promised: Ballot,
+    },
+    Decide {
+        slot: u64,
+        proposal: Proposal<C>,
+    },
+    Heartbeat {
+        ballot: Ballot,
+        decided: u64,
+    },
+    Catchup {
+        from_slot: u64,
+    },
+    CatchupOk {
+        /// the slot a snapshot covers everything before, and its state
+        snapshot: Option<(u64, Value)>,
+        decided: Vec<(u64, Proposal<C>)>,
+    },
+    Tick,
+}
+
+impl<C> TryFrom<Message> for Command<C>
+where
+    C: TryFrom<Value, Error = Error>,
+{
+    type Error = Error;
+
+    fn try_from(value: Message) -> Result<Self, Self::Error> {
+        let body = value.body();
+        match value.msg_type() {
+            "prepare" => Ok(Command::Prepare {
+                ballot: ballot(body, "ballot")?,
+                from_slot: field(body, "from_slot")?,
+            }),
+            "promise" => promise(body),
+            "accept" => Ok(Command::Accept {
+                ballot: ballot(body, "ballot")?,
+                slot: field(body, "slot")?,
+                proposal: Proposal::try_from(&body["proposal"])?,
+            }),
+            "accepted" => Ok(Command::Accepted {
+                ballot: ballot(body, "ballot")?,
+                slot: field(body, "slot")?,
+            }),
+            "nack" => Ok(Command::Nack {
+                promised: ballot(body, "promised")?,
+            }),
+            "decide" => Ok(Command::Decide {
+                slot: field(body, "slot")?,
+                proposal: Proposal::try_from(&body["proposal"])?,
+            }),
+            "heartbeat" => Ok(Command::Heartbeat {
+                ballot: ballot(body, "ballot")?,
+                decided: field(body, "decided")?,
+            }),
+            "catchup" => Ok(Command::Catchup {
+                from_slot: field(body, "from_slot")?,
+            }),
+            "catchup_ok" => catchup_ok(body),
+            "paxos_tick" => Ok(Command::Tick),
+            _ => C::try_from(body.clone()).map(Command::Client),
+        }
+    }
+}
+
+/// Parses `promise` messages, whose `accepted` key lists
+/// `[slot, ballot, proposal]` triples.
+fn promise<C>(body: &Value) -> Result<Command<C>, Error>
+where
+    C: TryFrom<Value, Error = Error>,
+{
+    let accepted = array(body, "accepted")?
+        .iter()
+        .map(|entry| match entry[0].as_u64() {
+            Some(slot) => Ok((
+                slot,
+                Ballot::try_from(&entry[1])?,
+                Proposal::try_from(&entry[2])?,
+            )),
+            None => Err(Error::malformed_request("accepted entry missing slot")),
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Command::Promise {
+        ballot: ballot(body, "ballot")?,
+        accepted,
+    })
+}
+
+/// Parses `catchup_ok` messages, whose `decided` key lists `[slot, proposal]`
+/// pairs, preceded by an optional `{"slot", "state"}` snapshot.
+fn catchup_ok<C>(body: &Value) -> Result<Command<C>, Error>
+where
+    C: TryFrom<Value, Error = Error>,
+{
+    let decided = array(body, "decided")?
+        .iter()
+        .map(|entry| match entry[0].as_u64() {
+            Some(slot) => Ok((slot, Proposal::try_from(&entry[1])?)),
+            None => Err(Error::malformed_request("decided entry missing slot")),
+        })
+        .collect::<Result<_, _>>()?;
+
+    let snapshot = match &body["snapshot"] {
+        Value::Null => None,
+        snapshot => Some((field(snapshot, "slot")?, snapshot["state"].clone())),
+    };
+
+    Ok(Command::CatchupOk { snapshot, decided })
+}
+
+fn ballot(body: &Value, name: &str) -> Result<Ballot, Error> {
+    Ballot::try_from(&body[name])
+}
+
+fn array<'a>(body: &'a Value, name: &str) -> Result<&'a Vec<Value>, Error> {
+    body[name]
+        .as_array()
+        .ok_or_else(|| Error::malformed_request(&format!("message missing `{}` key", name)))
+}
+
+fn field(body: &Value, name: &str) -> Result<u64, Error> {
+    body[name]
+        .as_u64()
+        .ok_or_else(|| Error::malformed_request(&format!("message missing `{}` key", name)))
+}
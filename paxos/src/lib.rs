@@ -0,0 +1,25 @@
+//! A [Multi-Paxos] replicated log for [Maelstrom] nodes.
+//!
+//! [Paxos] replicates any [StateMachine] across the cluster. A stable leader
+//! runs phase 1 (`prepare`/`promise`) once for all future log slots when it
+//! takes over with a new [Ballot], recovering values other leaders may have
+//! gotten accepted, and then runs only phase 2 (`accept`/`accepted`) for each
+//! client request. Chosen values are announced with `decide`, and nodes that
+//! missed some catch up from the leader, which advertises its progress in
+//! heartbeats. Client requests sent to other nodes are forwarded to the
+//! leader.
+//!
+//! Like in the `raft` crate, applied slots are periodically compacted into a
+//! state machine snapshot, which the leader sends to nodes that need slots
+//! it no longer has.
+//!
+//! [Multi-Paxos]: https://lamport.azurewebsites.net/pubs/paxos-simple.pdf
+//! [Maelstrom]: https://github.com/jepsen-io/maelstrom/tree/main
+mod ballot;
+mod command;
+mod paxos;
+
+pub use ballot::*;
+pub use command::*;
+pub use maelstrom::StateMachine;
+pub use paxos::*;
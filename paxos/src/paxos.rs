@@ -0,0 +1,702 @@
+use crate::{
+    ballot::Ballot,
+    command::{Command, Proposal},
+};
+use maelstrom::{Context, Error, Handler, StateMachine};
+use rand::Rng;
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+/// Timing and sizing parameters for [Paxos].
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// How often the node checks its election and heartbeat timers.
+    pub tick_interval: Duration,
+
+    /// Nodes try to become leader if they don't hear from one within a
+    /// random timeout between this and twice this.
+    pub election_timeout: Duration,
+
+    /// How often the leader sends heartbeats, and resends `accept` messages
+    /// for slots that aren't chosen yet.
+    pub heartbeat_interval: Duration,
+
+    /// The maximum number of decided slots sent in a single `catchup_ok`.
+    pub max_catchup: usize,
+
+    /// How long a node waits for the leader to answer a forwarded client
+    /// request.
+    pub forward_timeout: Duration,
+
+    /// How many applied slots may be kept before they are compacted into a
+    /// snapshot.
+    pub snapshot_threshold: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tick_interval: Duration::from_millis(10),
+            election_timeout: Duration::from_millis(500),
+            heartbeat_interval: Duration::from_millis(50),
+            max_catchup: 128,
+            forward_timeout: Duration::from_secs(1),
+            snapshot_threshold: 1024,
+        }
+    }
+}
+
+enum Role<C> {
+    Follower,
+    Candidate {
+        ballot: Ballot,
+        promises: HashSet<String>,
+
+        /// the highest-ballot proposal accepted for each slot by the nodes
+        /// that promised so far
+        accepted: BTreeMap<u64, (Ballot, Proposal<C>)>,
+    },
+    Leader {
+        ballot: Ballot,
+        next_slot: u64,
+
+        /// proposals that aren't chosen yet, with the nodes that accepted
+        /// them
+        proposals: BTreeMap<u64, (Proposal<C>, HashSet<String>)>,
+    },
+}
+
+/// A [Handler] that replicates a [StateMachine] with Multi-Paxos. See the
+/// [crate docs](crate).
+///
+/// Every node plays all three Paxos roles: it is an acceptor and a learner,
+/// and a proposer while it is leader.
+pub struct Paxos<S: StateMachine> {
+    config: Config,
+    state_machine: S,
+    role: Role<S::Command>,
+    leader: Option<String>,
+
+    /// the highest ballot this node has promised not to go below
+    promised: Ballot,
+
+    /// the latest proposal accepted for each slot from the snapshot slot on,
+    /// with the ballot it was accepted in
+    accepted: BTreeMap<u64, (Ballot, Proposal<S::Command>)>,
+
+    /// proposals known to be chosen, by slot, from the snapshot slot on
+    decided: BTreeMap<u64, Proposal<S::Command>>,
+
+    /// all slots before this one have been applied
+    applied: u64,
+
+    /// the state machine snapshot covering all slots before `snapshot_slot`
+    snapshot: Value,
+    snapshot_slot: u64,
+
+    election_deadline: Instant,
+    last_heartbeat: Instant,
+
+    /// clients waiting for a proposal, by slot, with the proposal id
+    pending: HashMap<u64, (String, Context)>,
+    proposal_count: u64,
+}
+
+impl<S: StateMachine> Paxos<S> {
+    pub fn new(state_machine: S) -> Self {
+        Self::with_config(state_machine, Config::default())
+    }
+
+    pub fn with_config(state_machine: S, config: Config) -> Self {
+        let election_deadline = election_deadline(&config);
+        let snapshot = state_machine.snapshot();
+
+        Self {
+            config,
+            state_machine,
+            role: Role::Follower,
+            leader: None,
+            promised: Ballot::default(),
+            accepted: BTreeMap::new(),
+            decided: BTreeMap::new(),
+            applied: 0,
+            snapshot,
+            snapshot_slot: 0,
+            election_deadline,
+            last_heartbeat: Instant::now(),
+            pending: HashMap::new(),
+            proposal_count: 0,
+        }
+    }
+
+    /// The replicated state machine.
+    pub fn state_machine(&self) -> &S {
+        &self.state_machine
+    }
+
+    fn client(&mut self, command: S::Command, ctx: Context) {
+        match (&self.role, &self.leader) {
+            (Role::Leader { .. }, _) => {
+                self.proposal_count += 1;
+                let id = format!("{}-{}", ctx.node_id(), self.proposal_count);
+                let command = Some(command);
+                if let Some(slot) = self.propose(
+                    Proposal {
+                        id: id.clone(),
+                        command,
+                    },
+                    &ctx,
+                ) {
+                    self.pending.insert(slot, (id, ctx));
+                }
+            }
+            (_, Some(leader)) => {
+                let timeout = self.config.forward_timeout;
+                ctx.forward(leader.clone(), command, timeout)
+            }
+            (_, None) => ctx.reply(Error::temporarily_unavailable("no leader elected")),
+        }
+    }
+
+    fn tick(&mut self, ctx: Context) {
+        let now = Instant::now();
+        match self.role {
+            Role::Leader { .. } if now >= self.last_heartbeat + self.config.heartbeat_interval => {
+                self.heartbeat(&ctx)
+            }
+            Role::Leader { .. } => {}
+            _ if now >= self.election_deadline => self.become_candidate(&ctx),
+            _ => {}
+        }
+    }
+
+    fn prepare(&mut self, ballot: Ballot, from_slot: u64, ctx: Context) {
+        self.observe_ballot(&ballot);
+
+        if ballot != self.promised {
+            return nack(&self.promised, &ctx);
+        }
+
+        // what we accepted in compacted slots is gone, so a candidate that
+        // hasn't learned them must catch up before it can recover the rest
+        if from_slot < self.snapshot_slot {
+            return self.catchup(from_slot, ctx);
+        }
+
+        // give the candidate a chance to finish its election
+        self.leader = None;
+        self.election_deadline = election_deadline(&self.config);
+
+        let accepted = self
+            .accepted
+            .range(from_slot..)
+            .map(|(slot, (ballot, proposal))| {
+                json!([slot, Value::from(ballot), Value::from(proposal)])
+            })
+            .collect::<Value>();
+        ctx.reply(json!({"type": "promise", "ballot": Value::from(&ballot), "accepted": accepted}));
+    }
+
+    fn promise(
+        &mut self,
+        ballot: Ballot,
+        accepted: Vec<(u64, Ballot, Proposal<S::Command>)>,
+        ctx: Context,
+    ) {
+        let Role::Candidate {
+            ballot: candidate_ballot,
+            promises,
+            accepted: recovered,
+        } = &mut self.role
+        else {
+            return;
+        };
+
+        if ballot != *candidate_ballot {
+            return;
+        }
+
+        promises.insert(ctx.src().to_string());
+        for (slot, ballot, proposal) in accepted {
+            match recovered.get(&slot) {
+                Some((highest, _)) if *highest >= ballot => {}
+                _ => {
+                    recovered.insert(slot, (ballot, proposal));
+                }
+            }
+        }
+
+        if promises.len() >= majority(&ctx) {
+            self.become_leader(&ctx);
+        }
+    }
+
+    fn accept(&mut self, ballot: Ballot, slot: u64, proposal: Proposal<S::Command>, ctx: Context) {
+        self.observe_ballot(&ballot);
+
+        if ballot != self.promised {
+            return nack(&self.promised, &ctx);
+        }
+
+        self.follow(&ctx);
+        self.accepted.insert(slot, (ballot.clone(), proposal));
+        ctx.reply(json!({"type": "accepted", "ballot": Value::from(&ballot), "slot": slot}));
+    }
+
+    fn accepted(&mut self, ballot: Ballot, slot: u64, ctx: Context) {
+        let Role::Leader {
+            ballot: leader_ballot,
+            proposals,
+            ..
+        } = &mut self.role
+        else {
+            return;
+        };
+
+        if ballot != *leader_ballot {
+            return;
+        }
+
+        if let Some((_, acceptors)) = proposals.get_mut(&slot) {
+            acceptors.insert(ctx.src().to_string());
+            self.check_chosen(slot, &ctx);
+        }
+    }
+
+    fn nack(&mut self, promised: Ballot) {
+        if self.observe_ballot(&promised) {
+            self.election_deadline = election_deadline(&self.config);
+        }
+    }
+
+    fn heartbeat_received(&mut self, ballot: Ballot, decided: u64, ctx: Context) {
+        self.observe_ballot(&ballot);
+
+        if ballot != self.promised {
+            return nack(&self.promised, &ctx);
+        }
+
+        self.follow(&ctx);
+        if decided > self.applied {
+            let request = json!({"type": "catchup", "from_slot": self.applied});
+            ctx.send(ctx.src().to_string(), None, request);
+        }
+    }
+
+    /// Sends decided slots from `from_slot` on, preceded by the snapshot if
+    /// some of them were compacted.
+    fn catchup(&mut self, from_slot: u64, ctx: Context) {
+        let snapshot = (from_slot < self.snapshot_slot)
+            .then(|| json!({"slot": self.snapshot_slot, "state": self.snapshot}));
+        let decided = self
+            .decided
+            .range(from_slot.max(self.snapshot_slot)..)
+            .take(self.config.max_catchup)
+            .map(|(slot, proposal)| json!([slot, Value::from(proposal)]))
+            .collect::<Value>();
+        ctx.reply(json!({"type": "catchup_ok", "snapshot": snapshot, "decided": decided}));
+    }
+
+    /// Replaces the state machine with a snapshot covering all slots before
+    /// `slot`, unless they are applied already.
+    fn install_snapshot(&mut self, slot: u64, state: Value) {
+        if slot <= self.applied {
+            return;
+        }
+
+        self.state_machine.restore(state.clone());
+        self.snapshot = state;
+        self.snapshot_slot = slot;
+        self.applied = slot;
+        self.decided = self.decided.split_off(&slot);
+        self.accepted = self.accepted.split_off(&slot);
+
+        // whatever happened to these is now folded into the snapshot
+        let pending = self.pending.keys().copied().collect::<Vec<_>>();
+        for slot in pending.into_iter().filter(|s| *s < slot) {
+            if let Some((_, ctx)) = self.pending.remove(&slot) {
+                ctx.reply(Error::timeout("request outcome unknown"));
+            }
+        }
+    }
+
+    fn become_candidate(&mut self, ctx: &Context) {
+        let ballot = self.promised.next(ctx.node_id());
+        self.promised = ballot.clone();
+        self.leader = None;
+        self.election_deadline = election_deadline(&self.config);
+
+        let accepted = self
+            .accepted
+            .range(self.applied..)
+            .map(|(slot, accepted)| (*slot, accepted.clone()))
+            .collect();
+        self.role = Role::Candidate {
+            ballot: ballot.clone(),
+            promises: HashSet::from([ctx.node_id().to_string()]),
+            accepted,
+        };
+
+        if majority(ctx) == 1 {
+            self.become_leader(ctx);
+            return;
+        }
+
+        let request =
+            json!({"type": "prepare", "ballot": Value::from(&ballot), "from_slot": self.applied});
+        for peer in peers(ctx) {
+            ctx.send(peer.to_string(), None, request.clone());
+        }
+    }
+
+    /// Takes over as leader once a majority has promised, re-proposing every
+    /// slot that isn't known to be decided: with the highest-ballot value
+    /// any of them accepted for it, or with a no-op if there is none.
+    fn become_leader(&mut self, ctx: &Context) {
+        let Role::Candidate {
+            ballot, accepted, ..
+        } = std::mem::replace(&mut self.role, Role::Follower)
+        else {
+            return;
+        };
+
+        let last_accepted = accepted.keys().next_back().map(|slot| slot + 1);
+        let last_decided = self.decided.keys().next_back().map(|slot| slot + 1);
+        let next_slot = [Some(self.applied), last_accepted, last_decided]
+            .into_iter()
+            .flatten()
+            .max()
+            .unwrap_or_default();
+
+        self.role = Role::Leader {
+            ballot,
+            next_slot,
+            proposals: BTreeMap::new(),
+        };
+        self.leader = Some(ctx.node_id().to_string());
+
+        for slot in self.applied..next_slot {
+            if self.decided.contains_key(&slot) {
+                continue;
+            }
+
+            let proposal = match accepted.get(&slot) {
+                Some((_, proposal)) => proposal.clone(),
+                None => Proposal {
+                    id: format!("noop-{}", slot),
+                    command: None,
+                },
+            };
+            self.send_accept(slot, proposal, ctx);
+        }
+
+        self.heartbeat(ctx);
+    }
+
+    /// Recognizes the sender of the current message as leader.
+    fn follow(&mut self, ctx: &Context) {
+        self.leader = Some(ctx.src().to_string());
+        self.election_deadline = election_deadline(&self.config);
+    }
+
+    /// Proposes a new value in the next free slot, returning the slot.
+    fn propose(&mut self, proposal: Proposal<S::Command>, ctx: &Context) -> Option<u64> {
+        let Role::Leader { next_slot, .. } = &mut self.role else {
+            return None;
+        };
+
+        let slot = *next_slot;
+        *next_slot += 1;
+        self.send_accept(slot, proposal, ctx);
+        Some(slot)
+    }
+
+    /// Accepts a proposal locally and asks all other nodes to accept it.
+    fn send_accept(&mut self, slot: u64, proposal: Proposal<S::Command>, ctx: &Context) {
+        let Role::Leader { ballot, .. } = &self.role else {
+            return;
+        };
+
+        let ballot = ballot.clone();
+        self.accepted
+            .insert(slot, (ballot.clone(), proposal.clone()));
+
+        let request = accept_request(&ballot, slot, &proposal);
+        for peer in peers(ctx) {
+            ctx.send(peer.to_string(), None, request.clone());
+        }
+
+        if let Role::Leader { proposals, .. } = &mut self.role {
+            let acceptors = HashSet::from([ctx.node_id().to_string()]);
+            proposals.insert(slot, (proposal, acceptors));
+        }
+        self.check_chosen(slot, ctx);
+    }
+
+    /// Decides the proposal in `slot` if a majority has accepted it, and
+    /// tells all other nodes.
+    fn check_chosen(&mut self, slot: u64, ctx: &Context) {
+        let Role::Leader { proposals, .. } = &mut self.role else {
+            return;
+        };
+
+        match proposals.get(&slot) {
+            Some((_, acceptors)) if acceptors.len() >= majority(ctx) => {}
+            _ => return,
+        }
+
+        if let Some((proposal, _)) = proposals.remove(&slot) {
+            let message =
+                json!({"type": "decide", "slot": slot, "proposal": Value::from(&proposal)});
+            for peer in peers(ctx) {
+                ctx.send(peer.to_string(), None, message.clone());
+            }
+            self.learn(slot, proposal);
+        }
+    }
+
+    /// Sends a heartbeat to all other nodes, and resends `accept` to nodes
+    /// that haven't accepted outstanding proposals.
+    fn heartbeat(&mut self, ctx: &Context) {
+        let Role::Leader {
+            ballot, proposals, ..
+        } = &self.role
+        else {
+            return;
+        };
+
+        let heartbeat =
+            json!({"type": "heartbeat", "ballot": Value::from(ballot), "decided": self.applied});
+        for peer in peers(ctx) {
+            ctx.send(peer.to_string(), None, heartbeat.clone());
+        }
+
+        for (slot, (proposal, acceptors)) in proposals {
+            let request = accept_request(ballot, *slot, proposal);
+            for peer in peers(ctx).filter(|peer| !acceptors.contains(*peer)) {
+                ctx.send(peer.to_string(), None, request.clone());
+            }
+        }
+
+        self.last_heartbeat = Instant::now();
+    }
+
+    /// Records that `proposal` was chosen for `slot`, then applies every
+    /// newly contiguous decided slot, compacting them into a snapshot once
+    /// there are enough.
+    fn learn(&mut self, slot: u64, proposal: Proposal<S::Command>) {
+        if slot < self.applied {
+            return;
+        }
+        self.decided.entry(slot).or_insert(proposal);
+
+        while let Some(proposal) = self.decided.get(&self.applied) {
+            let slot = self.applied;
+            self.applied += 1;
+
+            let reply = proposal
+                .command
+                .as_ref()
+                .map(|command| self.state_machine.apply(command));
+            match (self.pending.remove(&slot), reply) {
+                (Some((id, ctx)), Some(reply)) if id == proposal.id => ctx.reply(reply),
+                (Some((_, ctx)), _) => ctx.reply(Error::temporarily_unavailable(
+                    "slot taken by another proposal",
+                )),
+                (None, _) => {}
+            }
+        }
+
+        if self.applied - self.snapshot_slot >= self.config.snapshot_threshold {
+            self.snapshot = self.state_machine.snapshot();
+            self.snapshot_slot = self.applied;
+            self.decided = self.decided.split_off(&self.applied);
+            self.accepted = self.accepted.split_off(&self.applied);
+        }
+    }
+
+    /// Steps down if another node has a higher ballot, returning whether it
+    /// did.
+    fn observe_ballot(&mut self, ballot: &Ballot) -> bool {
+        if *ballot <= self.promised {
+            return false;
+        }
+
+        self.promised = ballot.clone();
+        self.leader = None;
+        self.role = Role::Follower;
+        self.abandon_pending();
+        true
+    }
+
+    /// Answers every waiting client after losing leadership. Their proposals
+    /// may still be chosen by the next leader, so the outcome is unknown.
+    fn abandon_pending(&mut self) {
+        for (_, (_, ctx)) in self.pending.drain() {
+            ctx.reply(Error::timeout("leadership changed"));
+        }
+    }
+}
+
+impl<S> Handler for Paxos<S>
+where
+    S: StateMachine,
+{
+    type Command = Command<S::Command>;
+
+    fn init(&mut self, ctx: Context) {
+        ctx.notify_every(self.config.tick_interval, json!({"type": "paxos_tick"}));
+    }
+
+    fn handle(&mut self, command: Self::Command, ctx: Context) {
+        match command {
+            Command::Client(command) => self.client(command, ctx),
+            Command::Prepare { ballot, from_slot } => self.prepare(ballot, from_slot, ctx),
+            Command::Promise { ballot, accepted } => self.promise(ballot, accepted, ctx),
+            Command::Accept {
+                ballot,
+                slot,
+                proposal,
+            } => self.accept(ballot, slot, proposal, ctx),
+            Command::Accepted { ballot, slot } => self.accepted(ballot, slot, ctx),
+            Command::Nack { promised } => self.nack(promised),
+            Command::Decide { slot, proposal } => self.learn(slot, proposal),
+            Command::Heartbeat { ballot, decided } => self.heartbeat_received(ballot, decided, ctx),
+            Command::Catchup { from_slot } => self.catchup(from_slot, ctx),
+            Command::CatchupOk { snapshot, decided } => {
+                if let Some((slot, state)) = snapshot {
+                    self.install_snapshot(slot, state);
+                }
+                for (slot, proposal) in decided {
+                    self.learn(slot, proposal);
+                }
+            }
+            Command::Tick => self.tick(ctx),
+        }
+    }
+}
+
+fn accept_request<C>(ballot: &Ballot, slot: u64, proposal: &Proposal<C>) -> Value
+where
+    C: Into<Value> + Clone,
+{
+    json!({
+        "type": "accept",
+        "ballot": Value::from(ballot),
+        "slot": slot,
+        "proposal": Value::from(proposal),
+    })
+}
+
+fn nack(promised: &Ballot, ctx: &Context) {
+    ctx.reply(json!({"type": "nack", "promised": Value::from(promised)}));
+}
+
+fn election_deadline(config: &Config) -> Instant {
+    let timeout = config.election_timeout;
+    let timeout = rand::thread_rng().gen_range(timeout..timeout * 2);
+    Instant::now() + timeout
+}
+
+fn majority(ctx: &Context) -> usize {
+    ctx.node_ids().len() / 2 + 1
+}
+
+fn peers(ctx: &Context) -> impl Iterator<Item = &String> {
+    ctx.node_ids().iter().filter(|id| *id != ctx.node_id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maelstrom::testing::{
+        counter::{add, cluster, converge, Counter, NODES},
+        Network,
+    };
+    use tokio::time::sleep;
+
+    async fn counters() -> (Network, Vec<Counter>) {
+        let config = Config {
+            election_timeout: Duration::from_millis(100),
+            heartbeat_interval: Duration::from_millis(20),
+            max_catchup: 4,
+            snapshot_threshold: 5,
+            ..Config::default()
+        };
+        cluster(|counter| Paxos::with_config(counter, config.clone())).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn replicates_commands_in_order() {
+        let (network, counters) = counters().await;
+
+        for (i, node_id) in NODES.iter().cycle().take(9).enumerate() {
+            let value = add(&network, node_id, 1).await;
+            assert_eq!(value, i as u64 + 1);
+        }
+
+        converge(&counters, 9).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn survives_losing_a_node() {
+        let (network, counters) = counters().await;
+        add(&network, "n0", 1).await;
+
+        for node_id in NODES {
+            network.isolate(node_id);
+            let reachable = NODES.iter().find(|id| **id != node_id).unwrap();
+            add(&network, reachable, 1).await;
+            network.heal();
+        }
+
+        converge(&counters, 4).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn answers_clients_of_deposed_leaders() {
+        let (network, counters) = counters().await;
+        add(&network, "n0", 1).await;
+
+        // whichever node leads gets a request it can't get chosen while
+        // isolated, and must still answer it once it learns of a new leader
+        for node_id in NODES {
+            network.isolate(node_id);
+            let body = json!({"type": "add", "delta": 1});
+            let client = network.clone();
+            let reply = tokio::spawn(async move {
+                client
+                    .try_request(node_id, body, Duration::from_secs(5))
+                    .await
+            });
+            sleep(Duration::from_millis(50)).await;
+            let reachable = NODES.iter().find(|id| **id != node_id).unwrap();
+            add(&network, reachable, 1).await;
+            network.heal();
+            let reply = reply.await.unwrap();
+            assert!(reply.is_some(), "{} never answered", node_id);
+        }
+
+        let total = counters[0].value();
+        converge(&counters, total).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn lagging_node_catches_up() {
+        let (network, counters) = counters().await;
+        add(&network, "n0", 1).await;
+        converge(&counters, 1).await;
+
+        network.isolate("n2");
+        for _ in 0..20 {
+            add(&network, "n0", 1).await;
+        }
+        assert_eq!(counters[2].value(), 1);
+
+        network.heal();
+        converge(&counters, 21).await;
+    }
+}
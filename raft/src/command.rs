@@ -0,0 +1,128 @@
+use crate::log::Entry;
+use maelstrom::{Error, Message};
+use serde_json::Value;
+
+/// The commands processed by [Raft](crate::Raft): client requests for the
+/// state machine, Raft's own RPCs, and timer ticks.
+pub enum Command<C> {
+    Client(C),
+    RequestVote {
+        term: u64,
+        last_log_index: u64,
+        last_log_term: u64,
+    },
+    RequestVoteOk {
+        term: u64,
+        vote_granted: bool,
+    },
+    AppendEntries {
+        term: u64,
+        prev_log_index: u64,
+        prev_log_term: u64,
+        entries: Vec<Entry<C>>,
+        leader_commit: u64,
+    },
+    AppendEntriesOk {
+        term: u64,
+        success: bool,
+        match_index: u64,
+    },
+    InstallSnapshot {
+        term: u64,
+        last_included_index: u64,
+        last_included_term: u64,
+        data: Value,
+    },
+    InstallSnapshotOk {
+        term: u64,
+        match_index: u64,
+    },
+    Tick,
+}
+
+impl<C> TryFrom<Message> for Command<C>
+where
+    C: TryFrom<Value, Error = Error>,
+{
+    type Error = Error;
+
+    fn try_from(value: Message) -> Result<Self, Self::Error> {
+        let body = value.body();
+        match value.msg_type() {
+            "request_vote" => request_vote(body),
+            "request_vote_ok" => request_vote_ok(body),
+            "append_entries" => append_entries(body),
+            "append_entries_ok" => append_entries_ok(body),
+            "install_snapshot" => install_snapshot(body),
+            "install_snapshot_ok" => install_snapshot_ok(body),
+            "raft_tick" => Ok(Command::Tick),
+            _ => C::try_from(body.clone()).map(Command::Client),
+        }
+    }
+}
+
+fn request_vote<C>(body: &Value) -> Result<Command<C>, Error> {
+    Ok(Command::RequestVote {
+        term: field(body, "term")?,
+        last_log_index: field(body, "last_log_index")?,
+        last_log_term: field(body, "last_log_term")?,
+    })
+}
+
+fn request_vote_ok<C>(body: &Value) -> Result<Command<C>, Error> {
+    Ok(Command::RequestVoteOk {
+        term: field(body, "term")?,
+        vote_granted: body["vote_granted"].as_bool().unwrap_or_default(),
+    })
+}
+
+fn append_entries<C>(body: &Value) -> Result<Command<C>, Error>
+where
+    C: TryFrom<Value, Error = Error>,
+{
+    let entries = match body["entries"].as_array() {
+        Some(entries) => entries
+            .iter()
+            .map(Entry::try_from)
+            .collect::<Result<_, _>>()?,
+        None => vec![],
+    };
+
+    Ok(Command::AppendEntries {
+        term: field(body, "term")?,
+        prev_log_index: field(body, "prev_log_index")?,
+        prev_log_term: field(body, "prev_log_term")?,
+        entries,
+        leader_commit: field(body, "leader_commit")?,
+    })
+}
+
+fn append_entries_ok<C>(body: &Value) -> Result<Command<C>, Error> {
+    Ok(Command::AppendEntriesOk {
+        term: field(body, "term")?,
+        success: body["success"].as_bool().unwrap_or_default(),
+        match_index: field(body, "match_index")?,
+    })
+}
+
+fn install_snapshot<C>(body: &Value) -> Result<Command<C>, Error> {
+    Ok(Command::InstallSnapshot {
+        term: field(body, "term")?,
+        last_included_index: field(body, "last_included_index")?,
+        last_included_term: field(body, "last_included_term")?,
+        data: body["data"].clone(),
+    })
+}
+
+fn install_snapshot_ok<C>(body: &Value) -> Result<Command<C>, Error> {
+    Ok(Command::InstallSnapshotOk {
+        term: field(body, "term")?,
+        match_index: field(body, "match_index")?,
+    })
+}
+
+fn field(body: &Value, name: &str) -> Result<u64, Error> {
+    body[name]
+        .as_u64()
+        .ok_or_else(|| Error::malformed_request(&format!("message missing `{}` key", name)))
+}
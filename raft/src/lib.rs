@@ -0,0 +1,20 @@
+//! A [Raft] consensus core for [Maelstrom] nodes.
+//!
+//! [Raft] replicates any [StateMachine] across the cluster: client requests
+//! are parsed into state machine commands, appended to the leader's log,
+//! committed once a majority has stored them, and applied on every node in
+//! log order. The leader replies to clients once their command is applied;
+//! other nodes forward client requests to the leader. Applied entries are
+//! periodically compacted into a snapshot, which the leader sends to
+//! followers that fall too far behind.
+//!
+//! [Raft]: https://raft.github.io/raft.pdf
+//! [Maelstrom]: https://github.com/jepsen-io/maelstrom/tree/main
+mod command;
+mod log;
+mod raft;
+
+pub use command::*;
+pub use log::Entry;
+pub use maelstrom::StateMachine;
+pub use raft::*;
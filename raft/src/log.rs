@@ -0,0 +1,191 @@
+use maelstrom::Error;
+use serde_json::{json, Value};
+
+/// A Raft log entry.
+#[derive(Clone, Debug)]
+pub struct Entry<C> {
+    pub term: u64,
+    pub command: C,
+}
+
+impl<C> TryFrom<&Value> for Entry<C>
+where
+    C: TryFrom<Value, Error = Error>,
+{
+    type Error = Error;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value["term"].as_u64() {
+            Some(term) => {
+                let command = C::try_from(value["command"].clone())?;
+                Ok(Self { term, command })
+            }
+            None => Err(Error::malformed_request("log entry missing `term` key")),
+        }
+    }
+}
+
+impl<C> From<&Entry<C>> for Value
+where
+    C: Into<Value> + Clone,
+{
+    fn from(entry: &Entry<C>) -> Self {
+        json!({"term": entry.term, "command": entry.command.clone().into()})
+    }
+}
+
+/// The Raft log. Indexes start at 1; entries up to the snapshot index have
+/// been compacted away and only their last index and term are kept.
+#[derive(Debug)]
+pub struct Log<C> {
+    snapshot_index: u64,
+    snapshot_term: u64,
+
+    /// entry `i` has index `snapshot_index + 1 + i`
+    entries: Vec<Entry<C>>,
+}
+
+impl<C> Log<C> {
+    pub fn new() -> Self {
+        Self {
+            snapshot_index: 0,
+            snapshot_term: 0,
+            entries: vec![],
+        }
+    }
+
+    /// The index of the last entry covered by the snapshot, or 0.
+    pub fn snapshot_index(&self) -> u64 {
+        self.snapshot_index
+    }
+
+    pub fn last_index(&self) -> u64 {
+        self.snapshot_index + self.entries.len() as u64
+    }
+
+    pub fn last_term(&self) -> u64 {
+        self.entries
+            .last()
+            .map_or(self.snapshot_term, |entry| entry.term)
+    }
+
+    /// The term of the entry at `index`, if it is known: either it is still
+    /// in the log, or it is the last entry covered by the snapshot.
+    pub fn term_at(&self, index: u64) -> Option<u64> {
+        if index == self.snapshot_index {
+            Some(self.snapshot_term)
+        } else {
+            self.get(index).map(|entry| entry.term)
+        }
+    }
+
+    /// The entry at `index`, unless it doesn't exist or was compacted.
+    pub fn get(&self, index: u64) -> Option<&Entry<C>> {
+        let offset = index.checked_sub(self.snapshot_index + 1)?;
+        self.entries.get(offset as usize)
+    }
+
+    /// Up to `max` entries starting at `index`, which must not be compacted.
+    pub fn entries_from(&self, index: u64, max: usize) -> &[Entry<C>] {
+        let start = (index - self.snapshot_index - 1) as usize;
+        let start = start.min(self.entries.len());
+        let end = (start + max).min(self.entries.len());
+        &self.entries[start..end]
+    }
+
+    pub fn push(&mut self, entry: Entry<C>) {
+        self.entries.push(entry);
+    }
+
+    /// Removes the entry at `index` and all that follow it.
+    pub fn truncate(&mut self, index: u64) {
+        let len = index.saturating_sub(self.snapshot_index + 1);
+        self.entries.truncate(len as usize);
+    }
+
+    /// Discards all entries up to and including `index`, which must be in the
+    /// log, recording them as covered by a snapshot.
+    pub fn compact(&mut self, index: u64) {
+        if let Some(term) = self.term_at(index) {
+            let count = (index - self.snapshot_index) as usize;
+            self.entries.drain(..count);
+            self.snapshot_index = index;
+            self.snapshot_term = term;
+        }
+    }
+
+    /// Discards the whole log, replacing it with a snapshot covering entries
+    /// up to `index`, whose last entry has `term`.
+    pub fn reset(&mut self, index: u64, term: u64) {
+        self.entries.clear();
+        self.snapshot_index = index;
+        self.snapshot_term = term;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(terms: &[u64]) -> Log<u64> {
+        let mut log = Log::new();
+        for (i, term) in terms.iter().enumerate() {
+            log.push(Entry {
+                term: *term,
+                command: i as u64 + 1,
+            });
+        }
+        log
+    }
+
+    #[test]
+    fn indexes_start_at_one() {
+        let log = log(&[1, 1, 2]);
+
+        assert_eq!(log.last_index(), 3);
+        assert_eq!(log.last_term(), 2);
+        assert_eq!(log.term_at(0), Some(0));
+        assert_eq!(log.term_at(3), Some(2));
+        assert_eq!(log.term_at(4), None);
+        assert_eq!(log.get(1).map(|e| e.command), Some(1));
+        assert!(log.get(0).is_none());
+    }
+
+    #[test]
+    fn truncate_removes_suffix() {
+        let mut log = log(&[1, 1, 2, 2]);
+        log.truncate(3);
+
+        assert_eq!(log.last_index(), 2);
+        assert_eq!(log.last_term(), 1);
+    }
+
+    #[test]
+    fn compact_keeps_indexes() {
+        let mut log = log(&[1, 1, 2, 3]);
+        log.compact(2);
+
+        assert_eq!(log.snapshot_index(), 2);
+        assert_eq!(log.last_index(), 4);
+        assert_eq!(log.term_at(2), Some(1));
+        assert_eq!(log.term_at(1), None);
+        assert!(log.get(2).is_none());
+        assert_eq!(log.get(3).map(|e| e.command), Some(3));
+
+        let commands = log.entries_from(3, 10).iter().map(|e| e.command);
+        assert_eq!(commands.collect::<Vec<_>>(), vec![3, 4]);
+
+        log.truncate(4);
+        assert_eq!(log.last_index(), 3);
+    }
+
+    #[test]
+    fn reset_discards_everything() {
+        let mut log = log(&[1, 1]);
+        log.reset(10, 4);
+
+        assert_eq!(log.last_index(), 10);
+        assert_eq!(log.last_term(), 4);
+        assert!(log.entries_from(11, 10).is_empty());
+    }
+}
@@ -0,0 +1,589 @@
+use crate::{
+    command::Command,
+    log::{Entry, Log},
+};
+use maelstrom::{Context, Error, Handler, StateMachine};
+use rand::Rng;
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+/// Timing and sizing parameters for [Raft].
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// How often the node checks its election and replication timers.
+    pub tick_interval: Duration,
+
+    /// Followers start an election if they don't hear from a leader within a
+    /// random timeout between this and twice this.
+    pub election_timeout: Duration,
+
+    /// How often the leader sends `append_entries` to followers, whether or
+    /// not there are new entries.
+    pub replication_interval: Duration,
+
+    /// The maximum number of entries sent in a single `append_entries`.
+    pub max_entries: usize,
+
+    /// How many applied entries the log may hold before it is compacted into
+    /// a snapshot.
+    pub snapshot_threshold: u64,
+
+    /// How long a follower waits for the leader to answer a forwarded client
+    /// request.
+    pub forward_timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tick_interval: Duration::from_millis(10),
+            election_timeout: Duration::from_millis(500),
+            replication_interval: Duration::from_millis(50),
+            max_entries: 128,
+            snapshot_threshold: 1024,
+            forward_timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+enum Role {
+    Follower,
+    Candidate {
+        votes: HashSet<String>,
+    },
+    Leader {
+        next_index: HashMap<String, u64>,
+        match_index: HashMap<String, u64>,
+    },
+}
+
+/// A [Handler] that replicates a [StateMachine] with Raft. See the
+/// [crate docs](crate).
+pub struct Raft<S: StateMachine> {
+    config: Config,
+    state_machine: S,
+    role: Role,
+    term: u64,
+    voted_for: Option<String>,
+    leader: Option<String>,
+
+    log: Log<S::Command>,
+    commit_index: u64,
+    last_applied: u64,
+
+    /// the state machine snapshot covering the log up to its snapshot index
+    snapshot: Value,
+
+    election_deadline: Instant,
+    last_replication: Instant,
+
+    /// clients waiting for the entry at an index, with the term it was
+    /// appended in
+    pending: HashMap<u64, (u64, Context)>,
+}
+
+impl<S: StateMachine> Raft<S> {
+    pub fn new(state_machine: S) -> Self {
+        Self::with_config(state_machine, Config::default())
+    }
+
+    pub fn with_config(state_machine: S, config: Config) -> Self {
+        let snapshot = state_machine.snapshot();
+        let election_deadline = election_deadline(&config);
+
+        Self {
+            config,
+            state_machine,
+            role: Role::Follower,
+            term: 0,
+            voted_for: None,
+            leader: None,
+            log: Log::new(),
+            commit_index: 0,
+            last_applied: 0,
+            snapshot,
+            election_deadline,
+            last_replication: Instant::now(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// The replicated state machine.
+    pub fn state_machine(&self) -> &S {
+        &self.state_machine
+    }
+
+    fn client(&mut self, command: S::Command, ctx: Context) {
+        match (&self.role, &self.leader) {
+            (Role::Leader { .. }, _) => {
+                let term = self.term;
+                self.log.push(Entry { term, command });
+                self.pending
+                    .insert(self.log.last_index(), (term, ctx.clone()));
+                self.advance_commit_index(&ctx);
+            }
+            (_, Some(leader)) => {
+                let timeout = self.config.forward_timeout;
+                ctx.forward(leader.clone(), command, timeout)
+            }
+            (_, None) => ctx.reply(Error::temporarily_unavailable("no leader elected")),
+        }
+    }
+
+    fn tick(&mut self, ctx: Context) {
+        let now = Instant::now();
+        match self.role {
+            Role::Leader { .. }
+                if now >= self.last_replication + self.config.replication_interval =>
+            {
+                self.replicate(&ctx)
+            }
+            Role::Leader { .. } => {}
+            _ if now >= self.election_deadline => self.become_candidate(&ctx),
+            _ => {}
+        }
+    }
+
+    fn request_vote(&mut self, term: u64, last_log_index: u64, last_log_term: u64, ctx: Context) {
+        self.observe_term(term);
+
+        let candidate = ctx.src();
+        let up_to_date =
+            (last_log_term, last_log_index) >= (self.log.last_term(), self.log.last_index());
+        let can_vote = self.voted_for.as_deref().is_none_or(|id| id == candidate);
+        let vote_granted = term == self.term && can_vote && up_to_date;
+
+        if vote_granted {
+            self.voted_for = Some(candidate.to_string());
+            self.election_deadline = election_deadline(&self.config);
+        }
+
+        let reply = json!({
+            "type": "request_vote_ok",
+            "term": self.term,
+            "vote_granted": vote_granted,
+        });
+        ctx.reply(reply);
+    }
+
+    fn request_vote_ok(&mut self, term: u64, vote_granted: bool, ctx: Context) {
+        self.observe_term(term);
+
+        if let Role::Candidate { votes } = &mut self.role {
+            if term == self.term && vote_granted {
+                votes.insert(ctx.src().to_string());
+                if votes.len() >= majority(&ctx) {
+                    self.become_leader(&ctx);
+                }
+            }
+        }
+    }
+
+    fn append_entries(
+        &mut self,
+        term: u64,
+        prev_log_index: u64,
+        prev_log_term: u64,
+        entries: Vec<Entry<S::Command>>,
+        leader_commit: u64,
+        ctx: Context,
+    ) {
+        self.observe_term(term);
+
+        if term == self.term {
+            self.follow(&ctx);
+        }
+
+        // entries covered by our snapshot are committed, so they match the
+        // leader's log by definition
+        let consistent = prev_log_index < self.log.snapshot_index()
+            || self.log.term_at(prev_log_index) == Some(prev_log_term);
+        let success = term == self.term && consistent;
+        let mut match_index = 0;
+
+        if success {
+            match_index = prev_log_index + entries.len() as u64;
+            for (offset, entry) in entries.into_iter().enumerate() {
+                let index = prev_log_index + 1 + offset as u64;
+                if index <= self.log.snapshot_index() {
+                    continue;
+                }
+
+                match self.log.term_at(index) {
+                    Some(term) if term == entry.term => continue,
+                    Some(_) => self.log.truncate(index),
+                    None => {}
+                }
+                self.log.push(entry);
+            }
+
+            if leader_commit > self.commit_index {
+                self.commit_index = leader_commit.min(match_index).max(self.commit_index);
+                self.apply();
+            }
+        }
+
+        let reply = json!({
+            "type": "append_entries_ok",
+            "term": self.term,
+            "success": success,
+            "match_index": match_index,
+        });
+        ctx.reply(reply);
+    }
+
+    fn append_entries_ok(&mut self, term: u64, success: bool, match_index: u64, ctx: Context) {
+        self.observe_term(term);
+
+        if term != self.term {
+            return;
+        }
+
+        if success {
+            self.replicated(match_index, &ctx);
+        } else if let Role::Leader { next_index, .. } = &mut self.role {
+            if let Some(next) = next_index.get_mut(ctx.src()) {
+                *next = next.saturating_sub(1).max(1);
+            }
+        }
+    }
+
+    fn install_snapshot(
+        &mut self,
+        term: u64,
+        last_included_index: u64,
+        last_included_term: u64,
+        data: Value,
+        ctx: Context,
+    ) {
+        self.observe_term(term);
+
+        if term < self.term {
+            let reply = json!({"type": "install_snapshot_ok", "term": self.term, "match_index": 0});
+            return ctx.reply(reply);
+        }
+
+        self.follow(&ctx);
+
+        if last_included_index > self.last_applied {
+            if self.log.term_at(last_included_index) == Some(last_included_term) {
+                self.log.compact(last_included_index);
+            } else {
+                self.log.reset(last_included_index, last_included_term);
+            }
+
+            self.state_machine.restore(data.clone());
+            self.snapshot = data;
+            self.last_applied = last_included_index;
+            self.commit_index = self.commit_index.max(last_included_index);
+
+            // whatever happened to these is now folded into the snapshot
+            let pending = self.pending.keys().copied().collect::<Vec<_>>();
+            for index in pending.into_iter().filter(|i| *i <= last_included_index) {
+                if let Some((_, ctx)) = self.pending.remove(&index) {
+                    ctx.reply(Error::timeout("request outcome unknown"));
+                }
+            }
+        }
+
+        let reply = json!({
+            "type": "install_snapshot_ok",
+            "term": self.term,
+            "match_index": last_included_index,
+        });
+        ctx.reply(reply);
+    }
+
+    fn install_snapshot_ok(&mut self, term: u64, match_index: u64, ctx: Context) {
+        self.observe_term(term);
+
+        if term == self.term {
+            self.replicated(match_index, &ctx);
+        }
+    }
+
+    fn become_candidate(&mut self, ctx: &Context) {
+        self.term += 1;
+        self.voted_for = Some(ctx.node_id().to_string());
+        self.leader = None;
+        self.election_deadline = election_deadline(&self.config);
+
+        let votes = HashSet::from([ctx.node_id().to_string()]);
+        let elected = votes.len() >= majority(ctx);
+        self.role = Role::Candidate { votes };
+
+        if elected {
+            self.become_leader(ctx);
+            return;
+        }
+
+        let request = json!({
+            "type": "request_vote",
+            "term": self.term,
+            "last_log_index": self.log.last_index(),
+            "last_log_term": self.log.last_term(),
+        });
+        for peer in peers(ctx) {
+            ctx.send(peer.to_string(), None, request.clone());
+        }
+    }
+
+    fn become_leader(&mut self, ctx: &Context) {
+        let next = self.log.last_index() + 1;
+        self.role = Role::Leader {
+            next_index: peers(ctx).map(|peer| (peer.to_string(), next)).collect(),
+            match_index: peers(ctx).map(|peer| (peer.to_string(), 0)).collect(),
+        };
+        self.leader = Some(ctx.node_id().to_string());
+        self.replicate(ctx);
+    }
+
+    /// Recognizes the sender of the current message as leader of the
+    /// current term.
+    fn follow(&mut self, ctx: &Context) {
+        self.role = Role::Follower;
+        self.leader = Some(ctx.src().to_string());
+        self.election_deadline = election_deadline(&self.config);
+    }
+
+    fn replicate(&mut self, ctx: &Context) {
+        let Role::Leader { next_index, .. } = &self.role else {
+            return;
+        };
+
+        for (peer, next) in next_index {
+            let request = if *next <= self.log.snapshot_index() {
+                json!({
+                    "type": "install_snapshot",
+                    "term": self.term,
+                    "last_included_index": self.log.snapshot_index(),
+                    "last_included_term": self.log.term_at(self.log.snapshot_index()),
+                    "data": self.snapshot,
+                })
+            } else {
+                let prev_log_index = next - 1;
+                let entries = self
+                    .log
+                    .entries_from(*next, self.config.max_entries)
+                    .iter()
+                    .map(Value::from)
+                    .collect::<Value>();
+
+                json!({
+                    "type": "append_entries",
+                    "term": self.term,
+                    "prev_log_index": prev_log_index,
+                    "prev_log_term": self.log.term_at(prev_log_index),
+                    "entries": entries,
+                    "leader_commit": self.commit_index,
+                })
+            };
+
+            ctx.send(peer.to_string(), None, request);
+        }
+
+        self.last_replication = Instant::now();
+    }
+
+    /// Records that the sender of the current message has replicated the log
+    /// up to `index`.
+    fn replicated(&mut self, index: u64, ctx: &Context) {
+        if let Role::Leader {
+            next_index,
+            match_index,
+        } = &mut self.role
+        {
+            let follower = ctx.src().to_string();
+            let matched = match_index.entry(follower.clone()).or_default();
+            *matched = index.max(*matched);
+            next_index.insert(follower, *matched + 1);
+            self.advance_commit_index(ctx);
+        }
+    }
+
+    /// Commits the highest index from the current term that a majority of
+    /// nodes have replicated, then applies newly committed entries.
+    fn advance_commit_index(&mut self, ctx: &Context) {
+        let Role::Leader { match_index, .. } = &self.role else {
+            return;
+        };
+
+        let mut indexes = match_index.values().copied().collect::<Vec<_>>();
+        indexes.push(self.log.last_index());
+        indexes.sort_unstable_by(|a, b| b.cmp(a));
+
+        let replicated = indexes[majority(ctx) - 1];
+        if replicated > self.commit_index && self.log.term_at(replicated) == Some(self.term) {
+            self.commit_index = replicated;
+            self.apply();
+        }
+    }
+
+    fn apply(&mut self) {
+        while self.last_applied < self.commit_index {
+            self.last_applied += 1;
+
+            let entry = self
+                .log
+                .get(self.last_applied)
+                .expect("committed entries are in the log");
+            let reply = self.state_machine.apply(&entry.command);
+
+            if let Some((term, ctx)) = self.pending.remove(&self.last_applied) {
+                if term == entry.term {
+                    ctx.reply(reply);
+                } else {
+                    ctx.reply(Error::temporarily_unavailable("leadership changed"));
+                }
+            }
+        }
+
+        if self.last_applied - self.log.snapshot_index() >= self.config.snapshot_threshold {
+            self.snapshot = self.state_machine.snapshot();
+            self.log.compact(self.last_applied);
+        }
+    }
+
+    /// Steps down to follower if another node has a newer term.
+    fn observe_term(&mut self, term: u64) {
+        if term > self.term {
+            self.term = term;
+            self.voted_for = None;
+            self.leader = None;
+            self.role = Role::Follower;
+        }
+    }
+}
+
+impl<S> Handler for Raft<S>
+where
+    S: StateMachine,
+{
+    type Command = Command<S::Command>;
+
+    fn init(&mut self, ctx: Context) {
+        ctx.notify_every(self.config.tick_interval, json!({"type": "raft_tick"}));
+    }
+
+    fn handle(&mut self, command: Self::Command, ctx: Context) {
+        match command {
+            Command::Client(command) => self.client(command, ctx),
+            Command::RequestVote {
+                term,
+                last_log_index,
+                last_log_term,
+            } => self.request_vote(term, last_log_index, last_log_term, ctx),
+            Command::RequestVoteOk { term, vote_granted } => {
+                self.request_vote_ok(term, vote_granted, ctx)
+            }
+            Command::AppendEntries {
+                term,
+                prev_log_index,
+                prev_log_term,
+                entries,
+                leader_commit,
+            } => self.append_entries(
+                term,
+                prev_log_index,
+                prev_log_term,
+                entries,
+                leader_commit,
+                ctx,
+            ),
+            Command::AppendEntriesOk {
+                term,
+                success,
+                match_index,
+            } => self.append_entries_ok(term, success, match_index, ctx),
+            Command::InstallSnapshot {
+                term,
+                last_included_index,
+                last_included_term,
+                data,
+            } => self.install_snapshot(term, last_included_index, last_included_term, data, ctx),
+            Command::InstallSnapshotOk { term, match_index } => {
+                self.install_snapshot_ok(term, match_index, ctx)
+            }
+            Command::Tick => self.tick(ctx),
+        }
+    }
+}
+
+fn election_deadline(config: &Config) -> Instant {
+    let timeout = config.election_timeout;
+    let timeout = rand::thread_rng().gen_range(timeout..timeout * 2);
+    Instant::now() + timeout
+}
+
+fn majority(ctx: &Context) -> usize {
+    ctx.node_ids().len() / 2 + 1
+}
+
+fn peers(ctx: &Context) -> impl Iterator<Item = &String> {
+    ctx.node_ids().iter().filter(|id| *id != ctx.node_id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maelstrom::testing::{
+        counter::{add, cluster, converge, Counter, NODES},
+        Network,
+    };
+
+    async fn counters() -> (Network, Vec<Counter>) {
+        let config = Config {
+            election_timeout: Duration::from_millis(100),
+            replication_interval: Duration::from_millis(20),
+            snapshot_threshold: 5,
+            ..Config::default()
+        };
+        cluster(|counter| Raft::with_config(counter, config.clone())).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn replicates_commands_in_order() {
+        let (network, counters) = counters().await;
+
+        for (i, node_id) in NODES.iter().cycle().take(9).enumerate() {
+            let value = add(&network, node_id, 1).await;
+            assert_eq!(value, i as u64 + 1);
+        }
+
+        converge(&counters, 9).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn survives_losing_a_node() {
+        let (network, counters) = counters().await;
+        add(&network, "n0", 1).await;
+
+        for node_id in NODES {
+            network.isolate(node_id);
+            let reachable = NODES.iter().find(|id| **id != node_id).unwrap();
+            add(&network, reachable, 1).await;
+            network.heal();
+        }
+
+        converge(&counters, 4).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn lagging_follower_installs_snapshot() {
+        let (network, counters) = counters().await;
+        add(&network, "n0", 1).await;
+        converge(&counters, 1).await;
+
+        network.isolate("n2");
+        for _ in 0..20 {
+            add(&network, "n0", 1).await;
+        }
+        assert_eq!(counters[2].value(), 1);
+
+        network.heal();
+        converge(&counters, 21).await;
+    }
+}
@@ -0,0 +1,27 @@
+use crate::op::Op;
+use maelstrom::{Error, Message};
+
+pub enum Command {
+    Txn(Vec<Op>),
+}
+
+impl TryFrom<Message> for Command {
+    type Error = Error;
+
+    fn try_from(value: Message) -> Result<Self, Self::Error> {
+        match value.msg_type() {
+            "txn" => txn(value),
+            msg_type => Err(Error::not_supported(msg_type)),
+        }
+    }
+}
+
+fn txn(message: Message) -> Result<Command, Error> {
+    match message.body()["txn"].as_array() {
+        Some(ops) => {
+            let ops = ops.iter().map(Op::try_from).collect::<Result<_, _>>()?;
+            Ok(Command::Txn(ops))
+        }
+        None => Err(Error::malformed_request("txn message missing `txn` key")),
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{command::Command, op::Op, store::transact};
+use maelstrom::{Context, Handler};
+use serde_json::{json, Value};
+use tokio::spawn;
+
+/// A list-append transaction processor backed by Maelstrom's `lin-kv` and
+/// `lww-kv` services. Nodes keep no data themselves; see [transact] for how
+/// transactions are stored.
+pub struct TxnHandler {
+    /// random, so that a restarted node doesn't reuse the transaction ids
+    /// it handed out before, whose thunks may still be referenced
+    session: u64,
+    next_txn: u64,
+}
+
+impl TxnHandler {
+    pub fn new() -> Self {
+        Self {
+            session: rand::random(),
+            next_txn: 0,
+        }
+    }
+
+    fn txn(&mut self, ops: Vec<Op>, ctx: Context) {
+        let txn_id = format!("{}-{:x}-{}", ctx.node_id(), self.session, self.next_txn);
+        self.next_txn += 1;
+
+        spawn(async move {
+            match transact(&ctx, &txn_id, ops).await {
+                Ok(ops) => {
+                    let txn = ops.iter().map(Value::from).collect::<Value>();
+                    ctx.reply(json!({"type": "txn_ok", "txn": txn}));
+                }
+                Err(error) => ctx.reply(error),
+            }
+        });
+    }
+}
+
+impl Handler for TxnHandler {
+    type Command = Command;
+
+    fn handle(&mut self, command: Command, ctx: Context) {
+        match command {
+            Command::Txn(ops) => self.txn(ops, ctx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maelstrom::{testing::Network, Error};
+
+    const NODES: [&str; 2] = ["n0", "n1"];
+
+    async fn cluster() -> Network {
+        let network = Network::new();
+        for node_id in NODES {
+            network.start(node_id, TxnHandler::new());
+        }
+        network.init(&NODES).await;
+        network
+    }
+
+    async fn txn(network: &Network, node_id: &str, txn: Value) -> Value {
+        let reply = network
+            .request(node_id, json!({"type": "txn", "txn": txn}))
+            .await;
+        assert_eq!(reply["type"], "txn_ok", "{}", reply);
+        reply["txn"].clone()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn appends_are_read_back_through_thunks() {
+        let network = cluster().await;
+        let appended = txn(
+            &network,
+            "n0",
+            json!([["append", 1, 10], ["append", 1, 11], ["r", 1, null]]),
+        )
+        .await;
+        assert_eq!(appended[2], json!(["r", 1, [10, 11]]));
+
+        // a restarted node starts its transaction ids afresh
+        network.stop("n0");
+        network.start("n0", TxnHandler::new());
+        network.init_node("n0", &NODES).await;
+        txn(&network, "n0", json!([["append", 2, 20]])).await;
+
+        let read = txn(
+            &network,
+            "n1",
+            json!([["r", 1, null], ["r", 2, null], ["r", 3, null]]),
+        )
+        .await;
+        assert_eq!(
+            read,
+            json!([["r", 1, [10, 11]], ["r", 2, [20]], ["r", 3, []]])
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn concurrent_appends_conflict_on_the_root() {
+        let network = cluster().await;
+        txn(&network, "n0", json!([["append", 1, 0]])).await;
+
+        let (mut appends, mut reads) = (vec![], vec![]);
+        for i in 1..=10 {
+            let node_id = NODES[i % 2];
+            let client = network.clone();
+            let body = json!({"type": "txn", "txn": [["append", 1, i]]});
+            appends.push(tokio::spawn(async move {
+                (i, client.request(node_id, body).await)
+            }));
+
+            let client = network.clone();
+            let body = json!({"type": "txn", "txn": [["r", 1, null]]});
+            reads.push(tokio::spawn(
+                async move { client.request(node_id, body).await },
+            ));
+        }
+
+        // reads don't swap the root, so they never conflict
+        for read in reads {
+            let reply = read.await.unwrap();
+            assert_eq!(reply["type"], "txn_ok", "{}", reply);
+        }
+
+        let mut committed = vec![json!(0)];
+        let mut conflicts = 0;
+        for append in appends {
+            let (i, reply) = append.await.unwrap();
+            if reply["type"] == "txn_ok" {
+                committed.push(json!(i));
+            } else {
+                assert_eq!(reply["code"], Error::TXN_CONFLICT, "{}", reply);
+                conflicts += 1;
+            }
+        }
+        assert!(conflicts > 0, "no transaction conflicted");
+
+        let read = txn(&network, "n0", json!([["r", 1, null]])).await;
+        let mut list = read[0][2].as_array().unwrap().clone();
+        list.sort_by_key(Value::to_string);
+        committed.sort_by_key(Value::to_string);
+        assert_eq!(list, committed);
+    }
+}
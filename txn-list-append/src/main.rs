@@ -0,0 +1,13 @@
+mod command;
+mod handler;
+mod op;
+mod store;
+
+use handler::TxnHandler;
+use maelstrom::Node;
+use tokio::task::JoinError;
+
+#[tokio::main]
+async fn main() -> Result<(), JoinError> {
+    Node::from_handler(TxnHandler::new()).start().await
+}
@@ -0,0 +1,38 @@
+use maelstrom::Error;
+use serde_json::{json, Value};
+
+/// A micro-operation in a `txn` request body.
+#[derive(Clone, Debug)]
+pub enum Op {
+    /// Reads the list at a key. The list is `None` until the read is executed.
+    Read(u64, Option<Vec<Value>>),
+
+    /// Appends a value to the list at a key.
+    Append(u64, Value),
+}
+
+impl TryFrom<&Value> for Op {
+    type Error = Error;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match (value[0].as_str(), value[1].as_u64(), &value[2]) {
+            (Some("r"), Some(key), _) => Ok(Op::Read(key, None)),
+            (Some("append"), Some(key), value) if !value.is_null() => {
+                Ok(Op::Append(key, value.clone()))
+            }
+            _ => Err(Error::malformed_request(&format!(
+                "invalid micro-operation: {}",
+                value
+            ))),
+        }
+    }
+}
+
+impl From<&Op> for Value {
+    fn from(op: &Op) -> Self {
+        match op {
+            Op::Read(key, list) => json!(["r", key, list]),
+            Op::Append(key, value) => json!(["append", key, value]),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use crate::op::Op;
+use maelstrom::{Context, Error, Kv};
+use serde_json::{Map, Value};
+use std::{
+    collections::{hash_map::Entry, BTreeSet, HashMap},
+    time::Duration,
+};
+use tokio::time::sleep;
+
+/// The `lin-kv` key holding the root of the database: a map from list keys
+/// to the ids of the thunks storing their current value.
+const ROOT: &str = "root";
+
+/// How many times to retry reading a thunk that isn't visible yet in
+/// `lww-kv`, and how long to wait between attempts.
+const THUNK_READ_ATTEMPTS: usize = 20;
+const THUNK_READ_DELAY: Duration = Duration::from_millis(10);
+
+/// Executes `ops` as a single transaction, returning them with reads filled
+/// in.
+///
+/// The database is a persistent map: list values are immutable thunks stored
+/// in `lww-kv` under unique ids, and a single root pointer in `lin-kv` maps
+/// keys to thunk ids. A transaction reads the root, writes new thunks for
+/// every list it appends to, and then swaps the root with a `cas`. If the
+/// root changed in the meantime the transaction aborts with `txn-conflict`.
+/// Since every committed transaction goes through one linearizable register,
+/// this gives strict serializability.
+///
+/// `txn_id` must be unique across the cluster; it is used to name new thunks.
+pub async fn transact(ctx: &Context, txn_id: &str, mut ops: Vec<Op>) -> Result<Vec<Op>, Error> {
+    let root = match Kv::lin().read(ctx, ROOT).await {
+        Ok(root) => Some(root),
+        Err(error) if error.code() == Error::KEY_DOES_NOT_EXIST => None,
+        Err(error) => return Err(error),
+    };
+
+    let pointers = root
+        .as_ref()
+        .and_then(|root| root.as_object())
+        .cloned()
+        .unwrap_or_default();
+
+    let mut lists = HashMap::new();
+    let mut appended = BTreeSet::new();
+
+    for op in ops.iter_mut() {
+        match op {
+            Op::Read(key, list) => {
+                let current = load(ctx, &pointers, &mut lists, *key).await?;
+                *list = Some(current.clone());
+            }
+            Op::Append(key, value) => {
+                let current = load(ctx, &pointers, &mut lists, *key).await?;
+                current.push(value.clone());
+                appended.insert(*key);
+            }
+        }
+    }
+
+    if appended.is_empty() {
+        return Ok(ops);
+    }
+
+    let mut new_pointers = pointers;
+    for key in appended {
+        let thunk_id = format!("{}-{}", txn_id, key);
+        Kv::lww()
+            .write(ctx, thunk_id.as_str(), lists[&key].clone())
+            .await?;
+        new_pointers.insert(key.to_string(), Value::from(thunk_id));
+    }
+
+    let create = root.is_none();
+    let from = root.unwrap_or_default();
+    match Kv::lin().cas(ctx, ROOT, from, new_pointers, create).await {
+        Ok(()) => Ok(ops),
+        Err(error) if error.code() == Error::PRECONDITION_FAILED => Err(Error::txn_conflict(
+            "database root changed during transaction",
+        )),
+        Err(error) => Err(error),
+    }
+}
+
+/// Gets the list at `key` as seen by the current transaction, loading it
+/// from its thunk on first access.
+async fn load<'a>(
+    ctx: &Context,
+    pointers: &Map<String, Value>,
+    lists: &'a mut HashMap<u64, Vec<Value>>,
+    key: u64,
+) -> Result<&'a mut Vec<Value>, Error> {
+    match lists.entry(key) {
+        Entry::Occupied(entry) => Ok(entry.into_mut()),
+        Entry::Vacant(entry) => {
+            let list = match pointers.get(&key.to_string()) {
+                Some(thunk_id) => read_thunk(ctx, thunk_id.clone()).await?,
+                None => vec![],
+            };
+            Ok(entry.insert(list))
+        }
+    }
+}
+
+/// Reads a thunk from `lww-kv`. Thunks are written before the root pointing
+/// to them, but `lww-kv` may not have made them visible everywhere yet, so
+/// missing thunks are retried for a while.
+async fn read_thunk(ctx: &Context, thunk_id: Value) -> Result<Vec<Value>, Error> {
+    let mut attempts = 0;
+
+    loop {
+        match Kv::lww().read(ctx, thunk_id.clone()).await {
+            Ok(Value::Array(list)) => return Ok(list),
+            Ok(value) => {
+                let text = format!("thunk {} is not a list: {}", thunk_id, value);
+                return Err(Error::malformed_request(&text));
+            }
+            Err(error) if error.code() == Error::KEY_DOES_NOT_EXIST => {
+                attempts += 1;
+                if attempts == THUNK_READ_ATTEMPTS {
+                    return Err(error);
+                }
+                sleep(THUNK_READ_DELAY).await;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
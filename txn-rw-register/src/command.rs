@@ -0,0 +1,38 @@
+use crate::txn::Txn;
+use maelstrom::{Error, Message};
+
+pub enum Command {
+    Txn(Txn),
+    Replicate(u64, u64),
+    ReplicateTxn(Txn),
+}
+
+impl TryFrom<Message> for Command {
+    type Error = Error;
+
+    fn try_from(value: Message) -> Result<Self, Self::Error> {
+        match value.msg_type() {
+            "txn" => txn(value).map(Command::Txn),
+            "replicate" => replicate(value),
+            "replicate_txn" => txn(value).map(Command::ReplicateTxn),
+            msg_type => Err(Error::not_supported(msg_type)),
+        }
+    }
+}
+
+fn txn(message: Message) -> Result<Txn, Error> {
+    match &message.body()["txn"] {
+        txn if txn.is_array() => Txn::try_from(txn),
+        _ => Err(Error::malformed_request("message missing `txn` key")),
+    }
+}
+
+fn replicate(message: Message) -> Result<Command, Error> {
+    let body = message.body();
+    match (body["key"].as_u64(), body["value"].as_u64()) {
+        (Some(key), Some(value)) => Ok(Command::Replicate(key, value)),
+        _ => Err(Error::malformed_request(
+            "replicate message missing `key` or `value` key",
+        )),
+    }
+}
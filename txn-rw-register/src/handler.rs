@@ -0,0 +1,199 @@
+use crate::{command::Command, txn::Txn};
+use maelstrom::{Context, Handler};
+use serde_json::{json, Value};
+use std::{collections::HashMap, str::FromStr};
+
+/// The isolation level provided by [TxnHandler].
+#[derive(Clone, Copy, Debug)]
+pub enum Isolation {
+    /// Every write is replicated to peers on its own, so peers may observe
+    /// some writes of a transaction but not others.
+    ReadUncommitted,
+
+    /// Transactions are replicated as a whole and applied atomically by
+    /// peers, so intermediate writes are never observed.
+    ReadCommitted,
+}
+
+impl FromStr for Isolation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read-uncommitted" => Ok(Self::ReadUncommitted),
+            "read-committed" => Ok(Self::ReadCommitted),
+            other => Err(format!("unknown isolation level: {}", other)),
+        }
+    }
+}
+
+/// A totally available read/write register store. Transactions execute
+/// against the local registers and are then replicated to all peers in the
+/// background: the client's reply doesn't wait for them, but the runtime
+/// retransmits each write until the peer acknowledges it, so writes reach
+/// nodes on the far side of a partition once it heals.
+pub struct TxnHandler {
+    isolation: Isolation,
+    registers: HashMap<u64, u64>,
+}
+
+impl TxnHandler {
+    pub fn new(isolation: Isolation) -> Self {
+        Self {
+            isolation,
+            registers: Default::default(),
+        }
+    }
+
+    fn txn(&mut self, mut txn: Txn, ctx: Context) {
+        txn.execute(&self.registers);
+        txn.apply(&mut self.registers);
+
+        let reply = json!({"type": "txn_ok", "txn": Value::from(&txn)});
+        ctx.reply(reply);
+
+        match self.isolation {
+            Isolation::ReadUncommitted => {
+                for (key, value) in txn.writes() {
+                    let replicate = json!({"type": "replicate", "key": key, "value": value});
+                    broadcast(replicate, &ctx);
+                }
+            }
+            Isolation::ReadCommitted => {
+                if txn.writes().next().is_some() {
+                    let replicate = json!({"type": "replicate_txn", "txn": Value::from(&txn)});
+                    broadcast(replicate, &ctx);
+                }
+            }
+        }
+    }
+
+    fn replicate(&mut self, key: u64, value: u64) {
+        self.registers.insert(key, value);
+    }
+
+    fn replicate_txn(&mut self, txn: Txn) {
+        txn.apply(&mut self.registers);
+    }
+}
+
+impl Handler for TxnHandler {
+    type Command = Command;
+
+    fn handle(&mut self, command: Command, ctx: Context) {
+        match command {
+            Command::Txn(txn) => self.txn(txn, ctx),
+            Command::Replicate(key, value) => self.replicate(key, value),
+            Command::ReplicateTxn(txn) => self.replicate_txn(txn),
+        }
+    }
+}
+
+fn broadcast(body: Value, ctx: &Context) {
+    let peers = ctx.node_ids().iter().filter(|id| *id != ctx.node_id());
+    for node_id in peers {
+        ctx.send_reliable(node_id.to_string(), body.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maelstrom::testing::Network;
+    use std::time::Duration;
+    use tokio::time::sleep;
+
+    const NODES: [&str; 3] = ["n0", "n1", "n2"];
+
+    async fn cluster(isolation: Isolation) -> Network {
+        let network = Network::new();
+        for node_id in NODES {
+            network.start(node_id, TxnHandler::new(isolation));
+        }
+        network.init(&NODES).await;
+        network
+    }
+
+    async fn txn(network: &Network, node_id: &str, txn: Value) -> Value {
+        let reply = network
+            .request(node_id, json!({"type": "txn", "txn": txn}))
+            .await;
+        assert_eq!(reply["type"], "txn_ok");
+        reply["txn"].clone()
+    }
+
+    /// Reads `keys` through `node_id` until they hold `expected`.
+    async fn converge(network: &Network, node_id: &str, keys: &[u64], expected: &[u64]) {
+        let reads = keys.iter().map(|key| json!(["r", key, null])).collect();
+        let expected = keys
+            .iter()
+            .zip(expected)
+            .map(|(key, value)| json!(["r", key, value]))
+            .collect();
+        let (reads, expected) = (Value::Array(reads), Value::Array(expected));
+
+        let mut read = Value::Null;
+        for _ in 0..100 {
+            read = txn(network, node_id, reads.clone()).await;
+            if read == expected {
+                return;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+        panic!("{} read {}, expected {}", node_id, read, expected);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn replicates_writes_to_every_peer() {
+        let network = cluster(Isolation::ReadUncommitted).await;
+        let read = txn(&network, "n0", json!([["w", 1, 10], ["r", 1, null]])).await;
+        assert_eq!(read, json!([["w", 1, 10], ["r", 1, 10]]));
+
+        // n2 misses the first send, and gets the write once it can
+        network.isolate("n2");
+        txn(&network, "n1", json!([["w", 2, 20]])).await;
+        converge(&network, "n0", &[1, 2], &[10, 20]).await;
+        network.heal();
+
+        for node_id in NODES {
+            converge(&network, node_id, &[1, 2], &[10, 20]).await;
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn peers_apply_committed_txns_atomically() {
+        let network = cluster(Isolation::ReadCommitted).await;
+        let writer = network.clone();
+        let writes = tokio::spawn(async move {
+            for i in 1..=20 {
+                txn(&writer, "n0", json!([["w", 1, i], ["w", 2, i]])).await;
+            }
+        });
+
+        // n1 sees each txn's writes together or not at all
+        while !writes.is_finished() {
+            let read = txn(&network, "n1", json!([["r", 1, null], ["r", 2, null]])).await;
+            assert_eq!(read[0][2], read[1][2], "n1 read {}", read);
+        }
+        writes.await.unwrap();
+
+        for node_id in NODES {
+            converge(&network, node_id, &[1, 2], &[20, 20]).await;
+        }
+    }
+
+    #[test]
+    fn parses_isolation_levels() {
+        assert!(matches!(
+            "read-uncommitted".parse(),
+            Ok(Isolation::ReadUncommitted)
+        ));
+        assert!(matches!(
+            "read-committed".parse(),
+            Ok(Isolation::ReadCommitted)
+        ));
+
+        let err = "serializable".parse::<Isolation>().unwrap_err();
+        assert_eq!(err, "unknown isolation level: serializable");
+    }
+}
@@ -0,0 +1,19 @@
+mod command;
+mod handler;
+mod op;
+mod txn;
+
+use handler::{Isolation, TxnHandler};
+use maelstrom::Node;
+use std::env;
+use tokio::task::JoinError;
+
+#[tokio::main]
+async fn main() -> Result<(), JoinError> {
+    let isolation = match env::var("TXN_ISOLATION") {
+        Ok(isolation) => isolation.parse().unwrap_or_else(|err| panic!("{}", err)),
+        Err(_) => Isolation::ReadUncommitted,
+    };
+
+    Node::from_handler(TxnHandler::new(isolation)).start().await
+}
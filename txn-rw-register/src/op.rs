@@ -0,0 +1,41 @@
+use maelstrom::Error;
+use serde_json::{json, Value};
+
+/// A micro-operation in a `txn` request body.
+#[derive(Clone, Debug)]
+pub enum Op {
+    /// Reads a key. The value is `None` until the read is executed, or when
+    /// the key has never been written.
+    Read(u64, Option<u64>),
+
+    /// Writes a value to a key.
+    Write(u64, u64),
+}
+
+impl TryFrom<&Value> for Op {
+    type Error = Error;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let op = (value[0].as_str(), value[1].as_u64(), &value[2]);
+        match op {
+            (Some("r"), Some(key), value) => Ok(Op::Read(key, value.as_u64())),
+            (Some("w"), Some(key), Value::Number(value)) => match value.as_u64() {
+                Some(value) => Ok(Op::Write(key, value)),
+                None => Err(Error::malformed_request("write value is not an integer")),
+            },
+            _ => Err(Error::malformed_request(&format!(
+                "invalid micro-operation: {}",
+                value
+            ))),
+        }
+    }
+}
+
+impl From<&Op> for Value {
+    fn from(op: &Op) -> Self {
+        match op {
+            Op::Read(key, value) => json!(["r", key, value]),
+            Op::Write(key, value) => json!(["w", key, value]),
+        }
+    }
+}
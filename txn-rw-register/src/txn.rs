@@ -0,0 +1,116 @@
+use crate::op::Op;
+use maelstrom::Error;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// A transaction: a sequence of micro-operations executed as a unit.
+#[derive(Clone, Debug)]
+pub struct Txn {
+    ops: Vec<Op>,
+}
+
+impl Txn {
+    /// Executes the reads in this transaction against `registers`, without
+    /// modifying them. Writes are buffered, so later reads in the transaction
+    /// observe earlier writes to the same key while other transactions don't
+    /// observe any of them until the transaction is [applied](Txn::apply).
+    pub fn execute(&mut self, registers: &HashMap<u64, u64>) {
+        let mut writes = HashMap::new();
+
+        for op in self.ops.iter_mut() {
+            match op {
+                Op::Read(key, value) => {
+                    *value = writes.get(key).or_else(|| registers.get(key)).copied()
+                }
+                Op::Write(key, value) => {
+                    writes.insert(*key, *value);
+                }
+            }
+        }
+    }
+
+    /// Applies all writes in this transaction to `registers`.
+    pub fn apply(&self, registers: &mut HashMap<u64, u64>) {
+        registers.extend(self.writes());
+    }
+
+    /// The writes in this transaction, in execution order.
+    pub fn writes(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.ops.iter().filter_map(|op| match op {
+            Op::Write(key, value) => Some((*key, *value)),
+            Op::Read(..) => None,
+        })
+    }
+}
+
+impl TryFrom<&Value> for Txn {
+    type Error = Error;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value.as_array() {
+            Some(ops) => {
+                let ops = ops.iter().map(Op::try_from).collect::<Result<_, _>>()?;
+                Ok(Self { ops })
+            }
+            None => Err(Error::malformed_request("transaction is not an array")),
+        }
+    }
+}
+
+impl From<&Txn> for Value {
+    fn from(txn: &Txn) -> Self {
+        txn.ops.iter().map(Value::from).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_and_encodes_micro_operations() {
+        let value = json!([["r", 1, null], ["w", 1, 5], ["r", 2, 7]]);
+        let txn = Txn::try_from(&value).unwrap();
+        assert_eq!(Value::from(&txn), value);
+        assert_eq!(txn.writes().collect::<Vec<_>>(), [(1, 5)]);
+
+        for malformed in [
+            json!({"r": 1}),
+            json!([["x", 1, 2]]),
+            json!([["r", "a", null]]),
+            json!([["w", 1, "a"]]),
+            json!([["w", 1, -1]]),
+        ] {
+            let err = Txn::try_from(&malformed).unwrap_err();
+            assert_eq!(err.code(), 12, "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn reads_observe_earlier_writes_in_the_same_txn() {
+        let mut registers = HashMap::from([(1, 1), (2, 2)]);
+        let value = json!([
+            ["r", 1, null],
+            ["w", 1, 10],
+            ["r", 1, null],
+            ["r", 2, null],
+            ["r", 3, null]
+        ]);
+        let mut txn = Txn::try_from(&value).unwrap();
+
+        txn.execute(&registers);
+        let executed = json!([
+            ["r", 1, 1],
+            ["w", 1, 10],
+            ["r", 1, 10],
+            ["r", 2, 2],
+            ["r", 3, null]
+        ]);
+        assert_eq!(Value::from(&txn), executed);
+        assert_eq!(registers[&1], 1, "writes are buffered until applied");
+
+        txn.apply(&mut registers);
+        assert_eq!(registers, HashMap::from([(1, 10), (2, 2)]));
+    }
+}
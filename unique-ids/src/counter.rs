@@ -0,0 +1,70 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::PathBuf,
+};
+
+/// How many values each write of the high-water mark reserves.
+const BLOCK_SIZE: u64 = 1000;
+
+/// A counter that never returns the same value twice, even across restarts.
+///
+/// Values are reserved in blocks: before handing out the first value of a
+/// block, the counter persists the end of the block, its high-water mark,
+/// to a file. After a restart it continues from the persisted mark,
+/// skipping whatever was left of the block in use when the process stopped.
+pub struct DurableCounter {
+    path: PathBuf,
+    next: u64,
+    reserved: u64,
+}
+
+impl DurableCounter {
+    /// Opens the counter persisted at `path`, starting from zero if the file
+    /// doesn't exist.
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        let reserved = match fs::read_to_string(&path) {
+            Ok(mark) => mark.trim().parse().map_err(|_| {
+                let text = format!("invalid high-water mark in {}", path.display());
+                io::Error::new(io::ErrorKind::InvalidData, text)
+            })?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => 0,
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self {
+            path,
+            next: reserved,
+            reserved,
+        })
+    }
+
+    pub fn next(&mut self) -> io::Result<u64> {
+        if self.next == self.reserved {
+            self.reserve(self.reserved + BLOCK_SIZE)?;
+        }
+
+        let value = self.next;
+        self.next += 1;
+        Ok(value)
+    }
+
+    /// Persists a new high-water mark. The mark is written to a temporary
+    /// file that replaces the old one once synced, so a crash at any point
+    /// leaves one of the two marks intact.
+    fn reserve(&mut self, mark: u64) -> io::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        write!(file, "{}", mark)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)?;
+
+        // make the rename itself durable
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            File::open(dir)?.sync_all()?;
+        }
+
+        self.reserved = mark;
+        Ok(())
+    }
+}
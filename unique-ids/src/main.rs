@@ -1,11 +1,20 @@
+mod counter;
+
+use counter::DurableCounter;
 use maelstrom::Node;
 use maelstrom::{Context, Error, Handler, Message};
 use serde_json::{json, Value};
+use std::{env, fs, path::PathBuf};
 use tokio::task::JoinError;
 
+/// Each node persists its id counter to a file named after it in
+/// `UNIQUE_IDS_DIR`, by default a `unique-ids` directory in the system's
+/// temporary directory.
 #[tokio::main]
 async fn main() -> Result<(), JoinError> {
-    Node::from_handler(GenerateHandler::default()).start().await
+    let dir = env::var_os("UNIQUE_IDS_DIR")
+        .map_or_else(|| env::temp_dir().join("unique-ids"), PathBuf::from);
+    Node::from_handler(GenerateHandler::new(dir)).start().await
 }
 
 struct Generate {
@@ -25,17 +34,41 @@ impl TryFrom<Message> for Generate {
     }
 }
 
-#[derive(Default)]
+/// Generates ids unique across the cluster by prefixing a per-node counter
+/// with the node id. The counter survives restarts, so a node restarted
+/// with the same id doesn't hand out ids it already has.
 struct GenerateHandler {
-    counter: u64,
+    dir: PathBuf,
+    counter: Option<DurableCounter>,
+}
+
+impl GenerateHandler {
+    fn new(dir: PathBuf) -> Self {
+        Self { dir, counter: None }
+    }
 }
 
 impl Handler for GenerateHandler {
     type Command = Generate;
 
+    fn init(&mut self, ctx: Context) {
+        let path = self.dir.join(format!("{}.ids", ctx.node_id()));
+        let counter = fs::create_dir_all(&self.dir).and_then(|_| DurableCounter::open(path));
+        match counter {
+            Ok(counter) => self.counter = Some(counter),
+            Err(err) => eprintln!("can't open id counter: {}", err),
+        }
+    }
+
     fn handle(&mut self, command: Self::Command, ctx: Context) {
-        let counter = self.counter;
-        self.counter += 1;
+        let counter = match self.counter.as_mut().map(DurableCounter::next) {
+            Some(Ok(counter)) => counter,
+            Some(Err(err)) => {
+                let text = format!("can't reserve ids: {}", err);
+                return ctx.reply(Error::temporarily_unavailable(&text));
+            }
+            None => return ctx.reply(Error::temporarily_unavailable("no id counter")),
+        };
 
         let node_id = command.node_id;
         let unique_id = format!("{}-{}", node_id, counter);
@@ -47,3 +80,38 @@ impl Handler for GenerateHandler {
         ctx.reply(reply);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maelstrom::testing::Network;
+    use std::collections::HashSet;
+
+    async fn generate(network: &Network) -> String {
+        let reply = network.request("n0", json!({"type": "generate"})).await;
+        reply["id"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn ids_stay_unique_across_restarts() {
+        let dir = env::temp_dir().join(format!("unique-ids-test-{}", std::process::id()));
+        let network = Network::new();
+        network.start("n0", GenerateHandler::new(dir.clone()));
+        network.init(&["n0"]).await;
+
+        let mut ids = HashSet::new();
+        for _ in 0..3 {
+            ids.insert(generate(&network).await);
+        }
+
+        network.stop("n0");
+        network.start("n0", GenerateHandler::new(dir.clone()));
+        network.init_node("n0", &["n0"]).await;
+        for _ in 0..3 {
+            let id = generate(&network).await;
+            assert!(ids.insert(id.clone()), "{} handed out twice", id);
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}